@@ -1,13 +1,23 @@
 use std::path::PathBuf;
+use std::time::Duration;
 
-use clap::{ArgMatches, Command, arg, value_parser};
+use clap::{ArgAction, ArgMatches, Command, arg, value_parser};
 use colored::Colorize;
+use mistletoe::cluster::{self, ClusterOptions};
 use mistletoe::command::*;
+use mistletoe::config::ConfigLayout;
 
 #[tokio::main]
 async fn main() {
     let matches = Command::new(env!("CARGO_CRATE_NAME"))
         .about("Polyglot Kubernetes package manager")
+        .arg(arg!(--kubeconfig <FILE> "kubeconfig file to use instead of $KUBECONFIG or ~/.kube/config")
+            .value_parser(value_parser!(PathBuf))
+            .global(true))
+        .arg(arg!(--context <CONTEXT> "kubeconfig context to use, defaults to the configured default context or else the current context")
+            .global(true))
+        .arg(arg!(--as <USER> "user to impersonate for cluster operations")
+            .global(true))
         .subcommand(
             Command::new("generate")
                 .about("Generate output YAML from a package")
@@ -20,6 +30,30 @@ async fn main() {
                 .arg(arg!(-s --set <VALUES> "set values to pass to the package"))
                 .arg(arg!(-o --output <TYPE> "output type, can be 'yaml', 'raw', or 'dir=<dirpath>'"))
                 .arg(arg!(-r --process "run the processing to set installation labels (will reformat the output YAML)"))
+                .arg(arg!(-n --namespace <NAMESPACE> "namespace to pass to the package, and to set on namespaced resources without one when processing"))
+        )
+        .subcommand(
+            Command::new("diff")
+                .about("Preview the changes installing or upgrading a package would make to the cluster")
+                .arg(arg!([name] "the name of the installation")
+                    .required(true))
+                .arg(arg!(-p --package <PACKAGE> "package to call")
+                    .required(true))
+                .arg(arg!(-f --inputfile <FILE> "input file containing values to pass to the package")
+                    .value_parser(value_parser!(PathBuf)))
+                .arg(arg!(-s --set <VALUES> "set values to pass to the package"))
+                .arg(arg!(-n --namespace <NAMESPACE> "namespace to install namespaced resources into when they don't specify one"))
+                .arg(arg!(--"field-manager" <NAME> "field manager to apply resources as (defaults to 'mistctl')"))
+                .arg(arg!(--"force-conflicts" <BOOL> "whether to take over fields owned by other field managers (defaults to true)")
+                    .value_parser(value_parser!(bool)))
+        )
+        .subcommand(
+            Command::new("drift")
+                .about("Find resources of an installation that were edited or deleted since it was deployed, failing if any were")
+                .arg(arg!([name] "the name of the installation")
+                    .required(true))
+                .arg(arg!(--rerender "compare against a fresh render of the recorded package and input instead of the stored one"))
+                .arg(arg!(--fix "re-apply the deployed revision to undo the drift"))
         )
         .subcommand(
             Command::new("install")
@@ -32,12 +66,79 @@ async fn main() {
                     .value_parser(value_parser!(PathBuf)))
                 .arg(arg!(-o --output <TYPE> "output type, can be 'details' or 'yaml'"))
                 .arg(arg!(-s --set <VALUES> "set values to pass to the package"))
+                .arg(arg!(-n --namespace <NAMESPACE> "namespace to install namespaced resources into when they don't specify one"))
+                .arg(arg!(--"create-namespace" "create the namespace given with --namespace if it doesn't exist")
+                    .requires("namespace"))
+                .arg(arg!(--"dry-run" <MODE> "only simulate the install, can be 'client' or 'server'"))
+                .arg(arg!(-w --wait "wait for the applied resources to become ready"))
+                .arg(arg!(--timeout <DURATION> "how long to wait for with --wait, e.g. '90s' or '5m' (defaults to 5m)"))
+                .arg(arg!(--atomic "undo the rollout if it fails, deleting the objects it created and restoring the previous revision (implies --wait)"))
+                .arg(arg!(--"skip-unknown-kinds" "skip resources whose kind the cluster doesn't serve instead of failing"))
+                .arg(arg!(--adopt "take over existing resources that don't belong to any installation"))
+                .arg(arg!(--"field-manager" <NAME> "field manager to apply resources as, saved for later rollouts of the installation (defaults to 'mistctl')"))
+                .arg(arg!(--"force-conflicts" <BOOL> "whether to take over fields owned by other field managers, saved for later rollouts of the installation (defaults to true)")
+                    .value_parser(value_parser!(bool)))
+        )
+        .subcommand(
+            Command::new("upgrade")
+                .about("Upgrade an installation, pruning resources the package no longer renders")
+                .arg(arg!([name] "the name of the installation")
+                    .required(true))
+                .arg(arg!(-p --package <PACKAGE> "package to call")
+                    .required(true))
+                .arg(arg!(-f --inputfile <FILE> "input file containing values to pass to the package")
+                    .value_parser(value_parser!(PathBuf)))
+                .arg(arg!(-s --set <VALUES> "set values to pass to the package"))
+                .arg(arg!(-n --namespace <NAMESPACE> "namespace to install namespaced resources into when they don't specify one"))
+                .arg(arg!(-w --wait "wait for the applied resources to become ready"))
+                .arg(arg!(--timeout <DURATION> "how long to wait for with --wait, e.g. '90s' or '5m' (defaults to 5m)"))
+                .arg(arg!(--atomic "undo the rollout if it fails, deleting the objects it created and restoring the previous revision (implies --wait)"))
+                .arg(arg!(--"skip-unknown-kinds" "skip resources whose kind the cluster doesn't serve instead of failing"))
+                .arg(arg!(--adopt "take over existing resources that don't belong to any installation"))
+                .arg(arg!(--"field-manager" <NAME> "field manager to apply resources as, saved for later rollouts of the installation (defaults to 'mistctl')"))
+                .arg(arg!(--"force-conflicts" <BOOL> "whether to take over fields owned by other field managers, saved for later rollouts of the installation (defaults to true)")
+                    .value_parser(value_parser!(bool)))
+        )
+        .subcommand(
+            Command::new("rollback")
+                .about("Roll an installation back to a previous revision")
+                .arg(arg!([name] "the name of the installation")
+                    .required(true))
+                .arg(arg!([revision] "the revision to roll back to, defaults to the previous deployed revision")
+                    .value_parser(value_parser!(u32)))
+                .arg(arg!(-w --wait "wait for the applied resources to become ready"))
+                .arg(arg!(--timeout <DURATION> "how long to wait for with --wait, e.g. '90s' or '5m' (defaults to 5m)"))
+                .arg(arg!(--"skip-unknown-kinds" "skip resources whose kind the cluster doesn't serve instead of failing"))
+                .arg(arg!(--"field-manager" <NAME> "field manager to apply resources as, saved for later rollouts of the installation (defaults to 'mistctl')"))
+                .arg(arg!(--"force-conflicts" <BOOL> "whether to take over fields owned by other field managers, saved for later rollouts of the installation (defaults to true)")
+                    .value_parser(value_parser!(bool)))
         )
         .subcommand(
             Command::new("uninstall")
                 .about("Uninstall a package from the cluster")
                 .arg(arg!([name] "the name of the installation")
                     .required(true))
+                .arg(arg!(-n --namespace <NAMESPACE> "namespace to look up namespaced resources in when they were tracked without one"))
+                .arg(arg!(--"dry-run" <MODE> "only simulate the uninstall, can be 'client' or 'server'"))
+                .arg(arg!(--scan "find the resources by scanning the cluster for installation labels instead of using the inventory"))
+                .arg(arg!(--"keep-kind" <KIND> "leave resources of the given kind in the cluster, can be repeated")
+                    .action(ArgAction::Append))
+                .arg(arg!(--cascade <MODE> "how dependents of deleted resources are treated, can be 'background', 'foreground' or 'orphan' (defaults to foreground)"))
+                .arg(arg!(-w --wait "wait for the deleted resources to be gone"))
+                .arg(arg!(--timeout <DURATION> "how long to wait for with --wait, e.g. '90s' or '5m' (defaults to 5m)"))
+        )
+        .subcommand(
+            Command::new("list")
+                .about("List the installations in the cluster")
+                .arg(arg!(-o --output <TYPE> "output type, can be 'table', 'yaml' or 'json'"))
+                .arg(arg!(--scan "also scan the cluster for labeled resources of installations without an inventory"))
+        )
+        .subcommand(
+            Command::new("status")
+                .about("Show the health of an installation's resources, failing unless it's healthy")
+                .arg(arg!([name] "the name of the installation")
+                    .required(true))
+                .arg(arg!(-o --output <TYPE> "output type, can be 'table', 'yaml' or 'json'"))
         )
         .subcommand(
             Command::new("inspect")
@@ -54,8 +155,20 @@ async fn main() {
                         .arg(arg!([name] "the name of the installation")
                             .required(true))
                         .arg(arg!(-o --output <TYPE> "output type, can be 'yaml' or 'list'"))
+                        .arg(arg!(--revision <REVISION> "only show resources applied by the given revision")
+                            .value_parser(value_parser!(u32)))
+                        .arg(arg!(--scan "find the resources by scanning the cluster for installation labels instead of using the inventory"))
+                        .arg(arg!(--"repair-inventory" "rebuild the installation's inventory from a scan of the cluster"))
                 )
         )
+        .subcommand(
+            Command::new("search")
+                .about("Search the configured registries for packages")
+                .arg(arg!([keyword] "text to look for in package paths, names and label values"))
+                .arg(arg!(-l --label <LABEL> "only show packages with the given label, e.g. 'mistletoe.dev/group=databases'")
+                    .action(ArgAction::Append))
+                .arg(arg!(-o --output <TYPE> "output type, can be 'table', 'yaml' or 'json'"))
+        )
         .subcommand(
             Command::new("registry")
                 .about("Manage the configured registries for Mistletoe")
@@ -66,6 +179,13 @@ async fn main() {
                             .required(true))
                         .arg(arg!(-g --git <URL> "a git remote url"))
                 )
+                .subcommand(
+                    Command::new("index")
+                        .about("Generates the index file for a registry's packages")
+                        .arg(arg!([path] "the root of the registry to index, defaults to the current directory")
+                            .value_parser(value_parser!(PathBuf)))
+                        .arg(arg!(--stdout "print the index instead of writing it to the registry root"))
+                )
                 .subcommand(
                     Command::new("list")
                         .about("Lists the configured registries")
@@ -81,22 +201,49 @@ async fn main() {
 
     if let Err(e) = run_cli(&matches).await {
         eprintln!("{}{} {}", "error".bold().red(), ":".bold(), e.to_string());
+        std::process::exit(1);
     }
 }
 
 async fn run_cli(matches: &ArgMatches) -> anyhow::Result<()> {
+    cluster::configure(cluster_options_from_matches(matches)?)?;
+
     if let Some(matches) = matches.subcommand_matches("generate") {
         generate::run_command(&matches)?;
     }
 
+    if let Some(matches) = matches.subcommand_matches("diff") {
+        diff::run_command(&matches).await?;
+    }
+
+    if let Some(matches) = matches.subcommand_matches("drift") {
+        drift::run_command(&matches).await?;
+    }
+
     if let Some(matches) = matches.subcommand_matches("install") {
         install::run_command(&matches).await?;
     }
 
+    if let Some(matches) = matches.subcommand_matches("upgrade") {
+        upgrade::run_command(&matches).await?;
+    }
+
+    if let Some(matches) = matches.subcommand_matches("rollback") {
+        rollback::run_command(&matches).await?;
+    }
+
     if let Some(matches) = matches.subcommand_matches("uninstall") {
         uninstall::run_command(&matches).await?;
     }
 
+    if let Some(matches) = matches.subcommand_matches("list") {
+        list::run_command(&matches).await?;
+    }
+
+    if let Some(matches) = matches.subcommand_matches("status") {
+        status::run_command(&matches).await?;
+    }
+
     if let Some(matches) = matches.subcommand_matches("inspect") {
         if let Some(matches) = matches.subcommand_matches("package") {
             inspect_package::run_command(&matches)?;
@@ -107,11 +254,19 @@ async fn run_cli(matches: &ArgMatches) -> anyhow::Result<()> {
         }
     }
 
+    if let Some(matches) = matches.subcommand_matches("search") {
+        search::run_command(matches)?;
+    }
+
     if let Some(matches) = matches.subcommand_matches("registry") {
         if let Some(matches) = matches.subcommand_matches("add") {
             registry_add::run_command(matches)?;
         }
 
+        if let Some(matches) = matches.subcommand_matches("index") {
+            registry_index::run_command(matches)?;
+        }
+
         if let Some(matches) = matches.subcommand_matches("list") {
             registry_list::run_command(&matches)?;
         }
@@ -123,3 +278,18 @@ async fn run_cli(matches: &ArgMatches) -> anyhow::Result<()> {
 
     Ok(())
 }
+
+fn cluster_options_from_matches(matches: &ArgMatches) -> anyhow::Result<ClusterOptions> {
+    let config = ConfigLayout::from_env()?;
+    let context = match matches.get_one::<String>("context") {
+        Some(context) => Some(context.clone()),
+        None => config.spec.default_context,
+    };
+
+    Ok(ClusterOptions {
+        kubeconfig: matches.get_one::<PathBuf>("kubeconfig").cloned(),
+        context,
+        impersonate: matches.get_one::<String>("as").cloned(),
+        discovery_cache_ttl: config.spec.discovery_cache_ttl.map(Duration::from_secs),
+    })
+}
@@ -0,0 +1,83 @@
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use k8s_openapi::api::core::v1::Namespace;
+use kube::{Api, Client, Config};
+use kube::config::{KubeConfigOptions, Kubeconfig};
+use kube::api::{ObjectMeta, Patch, PatchParams};
+
+static CLUSTER_OPTIONS: OnceLock<ClusterOptions> = OnceLock::new();
+
+/// Options for connecting to the cluster, set once from the command line before any
+/// cluster operation runs.
+#[derive(Clone, Default, Debug)]
+pub struct ClusterOptions {
+    /// Kubeconfig file to use in place of `$KUBECONFIG` or `~/.kube/config`.
+    pub kubeconfig: Option<PathBuf>,
+    /// Kubeconfig context to use in place of the current context.
+    pub context: Option<String>,
+    /// User to impersonate for every request.
+    pub impersonate: Option<String>,
+    /// How long discovery results are cached on disk for, if at all.
+    pub discovery_cache_ttl: Option<Duration>,
+}
+
+/// Sets the options every client is created with.  This can only be done once.
+pub fn configure(options: ClusterOptions) -> anyhow::Result<()> {
+    CLUSTER_OPTIONS.set(options)
+        .map_err(|_| anyhow!("the cluster options have already been configured"))
+}
+
+/// Returns the configured options, or the defaults if none were configured.
+pub fn options() -> ClusterOptions {
+    CLUSTER_OPTIONS.get().cloned().unwrap_or_default()
+}
+
+/// Loads the cluster config with the configured options applied.
+pub async fn config() -> anyhow::Result<Config> {
+    let options = options();
+    let kubeconfig_options = KubeConfigOptions {
+        context: options.context.clone(),
+        ..KubeConfigOptions::default()
+    };
+
+    let mut config = match (&options.kubeconfig, &options.context) {
+        (Some(kubeconfig), _) => Config::from_custom_kubeconfig(
+            Kubeconfig::read_from(kubeconfig)?, &kubeconfig_options).await?,
+        (None, Some(_)) => Config::from_kubeconfig(&kubeconfig_options).await?,
+        (None, None) => Config::infer().await?,
+    };
+
+    if let Some(user) = options.impersonate {
+        config.auth_info.impersonate = Some(user);
+    }
+
+    Ok(config)
+}
+
+/// Returns whether the namespace exists.
+pub async fn namespace_exists(client: &Client, name: &str) -> anyhow::Result<bool> {
+    Ok(Api::<Namespace>::all(client.clone()).get_opt(name).await?.is_some())
+}
+
+/// Creates the namespace if it doesn't exist yet.  Returns whether it was created.
+pub async fn ensure_namespace(client: &Client, name: &str) -> anyhow::Result<bool> {
+    if namespace_exists(client, name).await? {
+        return Ok(false);
+    }
+
+    let api = Api::<Namespace>::all(client.clone());
+
+    let namespace = Namespace {
+        metadata: ObjectMeta {
+            name: Some(name.to_string()),
+            ..ObjectMeta::default()
+        },
+        ..Namespace::default()
+    };
+    api.patch(name, &PatchParams::apply("mistctl"), &Patch::Apply(&namespace)).await?;
+
+    Ok(true)
+}
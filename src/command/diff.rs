@@ -0,0 +1,84 @@
+use crate::command::install::{apply_options_from_matches, input_from_matches, namespace_from_matches, note_resolved, resources_from_output};
+use crate::installation::{ClusterSession, InstallRef, ResourceChange, ResourceDiff};
+use crate::instance::{MistPackageInstance, MistPackageRef};
+
+use anyhow::anyhow;
+use clap::ArgMatches;
+use colored::Colorize;
+use kube::ResourceExt;
+use similar::TextDiff;
+
+pub async fn run_command(matches: &ArgMatches) -> anyhow::Result<()> {
+    let package = matches.get_one::<String>("package").unwrap();
+    let name = matches.get_one::<String>("name").ok_or(anyhow!("'name' must be provided"))?;
+
+    let session = ClusterSession::new().await?;
+    let namespace = namespace_from_matches(matches, &session, name).await?;
+    let input = input_from_matches(matches, name, namespace.as_deref())?;
+    let mut instance  = MistPackageInstance::load(&MistPackageRef::from_str(package)?)?;
+    note_resolved(package, &instance);
+    let output = instance.generate(&serde_yaml::to_string(&input)?)?;
+
+    let install_ref = InstallRef { name: name.to_string(), version: None };
+    let version = install_ref.get_next_version(&session).await?;
+    let install_resources = resources_from_output(&output, namespace.as_deref())?
+        .label_resources_with(name, Some(version))?
+        .split_hooks()?.0;
+
+    let diffs = install_ref.diff_resources(&session, &install_resources, &apply_options_from_matches(matches, &session, name).await?).await?;
+    output_diffs(&diffs);
+
+    Ok(())
+}
+
+fn output_diffs(diffs: &[ResourceDiff]) {
+    for diff in diffs {
+        if diff.change == ResourceChange::Unchanged { continue }
+        output_unified_diff(diff);
+    }
+
+    let count = |change: ResourceChange| diffs.iter().filter(|diff| diff.change == change).count();
+    println!("{} to create, {} to update, {} to prune on upgrade, {} unchanged",
+        count(ResourceChange::Create).to_string().green(),
+        count(ResourceChange::Update).to_string().yellow(),
+        count(ResourceChange::Prune).to_string().red(),
+        count(ResourceChange::Unchanged));
+}
+
+/// Prints a colored unified diff going from the live resource to the desired one.
+pub(crate) fn output_unified_diff(diff: &ResourceDiff) {
+    let resource = describe_resource(diff);
+    let live = diff.live.clone().unwrap_or_default();
+    let desired = diff.desired.clone().unwrap_or_default();
+
+    let unified_diff = TextDiff::from_lines(&live, &desired)
+        .unified_diff()
+        .context_radius(3)
+        .header(&format!("live/{}", resource), &format!("desired/{}", resource))
+        .to_string();
+
+    for line in unified_diff.lines() {
+        if line.starts_with("+++") || line.starts_with("---") {
+            println!("{}", line.bold());
+        } else if line.starts_with("@@") {
+            println!("{}", line.cyan());
+        } else if line.starts_with("+") {
+            println!("{}", line.green());
+        } else if line.starts_with("-") {
+            println!("{}", line.red());
+        } else {
+            println!("{}", line);
+        }
+    }
+}
+
+pub(crate) fn describe_resource(diff: &ResourceDiff) -> String {
+    let kind = diff.resource.types.as_ref()
+        .map(|types| types.kind.to_lowercase())
+        .unwrap_or("<unknown kind>".to_string());
+
+    match &diff.resource.metadata.namespace {
+        Some(namespace) => format!("{}/{}/{}", namespace, kind, diff.resource.name_any()),
+        None => format!("{}/{}", kind, diff.resource.name_any()),
+    }
+}
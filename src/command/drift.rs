@@ -0,0 +1,70 @@
+use crate::command::diff::{describe_resource, output_unified_diff};
+use crate::command::install::{apply_options_from_matches, warn_skipped};
+use crate::installation::{ClusterSession, InstallResources, InstallRef, InstallStatus, ResourceChange};
+use crate::instance::{MistPackageInstance, MistPackageRef};
+
+use anyhow::anyhow;
+use clap::ArgMatches;
+use colored::Colorize;
+
+pub async fn run_command(matches: &ArgMatches) -> anyhow::Result<()> {
+    let name = matches.get_one::<String>("name").unwrap();
+
+    let session = ClusterSession::new().await?;
+    let record = InstallRef { name: name.to_string(), version: None }
+        .get_records(&session).await?.into_iter().rev()
+        .find(|record| record.status == InstallStatus::Deployed)
+        .ok_or(anyhow!("installation \"{}\" has no deployed revision", name))?;
+
+    // The stored render is what was actually applied, but re-rendering the recorded package and
+    // input also catches changes that only show up in a fresh render.
+    let install_resources = if matches.get_flag("rerender") {
+        let mut instance = MistPackageInstance::load(&MistPackageRef::from_str(&record.package)?)?;
+        let output = instance.generate(&serde_yaml::to_string(&record.input)?)?;
+        let resources = InstallResources::from_output(&output)?
+            .label_resources_with(name, Some(record.version))?;
+
+        match record.input.data.get("namespace").and_then(|namespace| namespace.as_str()) {
+            Some(namespace) => resources.with_default_namespace(namespace),
+            None => resources,
+        }
+    } else {
+        InstallResources::from_str(&record.manifest)?
+    };
+    // Hooks come and go as they run, so they don't count as drift.
+    let install_resources = install_resources.split_hooks()?.0;
+
+    let install_ref = InstallRef { name: name.to_string(), version: Some(record.version) };
+    let options = apply_options_from_matches(matches, &session, name).await?;
+    let drift = install_ref.detect_drift(&session, &install_resources, &options).await?;
+
+    if drift.is_empty() {
+        println!("installation \"{}\" matches revision {}", name, record.version);
+        return Ok(());
+    }
+
+    for diff in &drift {
+        match diff.change {
+            ResourceChange::Create => println!("{} {}", "deleted".red(), describe_resource(diff)),
+            _ => {
+                println!("{} {}", "edited".yellow(), describe_resource(diff));
+                output_unified_diff(diff);
+            },
+        }
+    }
+
+    let deleted = drift.iter().filter(|diff| diff.change == ResourceChange::Create).count();
+    let edited = drift.len() - deleted;
+
+    if matches.get_flag("fix") {
+        let skipped = install_ref.apply_resources(&session, &install_resources, &options).await?;
+        warn_skipped(&skipped);
+
+        println!("re-applied revision {} of \"{}\" to fix {} edited and {} deleted resource(s)",
+            record.version, name, edited, deleted);
+        Ok(())
+    } else {
+        Err(anyhow!("installation \"{}\" has drifted from revision {}: {} edited, {} deleted",
+            name, record.version, edited, deleted))
+    }
+}
@@ -1,36 +1,20 @@
+use crate::command::install::{input_from_matches, note_resolved};
 use crate::instance::{MistPackageInstance, MistPackageRef};
 use crate::outputs::*;
 
-use std::fs;
 use std::path::PathBuf;
 
 use anyhow::anyhow;
 use clap::ArgMatches;
-use mistletoe_api::v1alpha1::{MistInput, MistResult};
+use mistletoe_api::v1alpha1::MistResult;
 
 pub fn run_command(matches: &ArgMatches) -> anyhow::Result<()> {
     let package = matches.get_one::<String>("package").unwrap();
     let process = matches.get_flag("process");
 
-    let input_file_yaml = if let Some(input_file) = matches.get_one::<PathBuf>("inputfile") {
-        let input_file_string = String::from_utf8(fs::read(input_file)?)?;
-        serde_yaml::from_str::<serde_yaml::Mapping>(&input_file_string)?
-    } else {
-        serde_yaml::from_str("{}")?
-    };
-
-    let input_sets_yaml = if let Some(input_sets) = matches.get_one::<String>("set") {
-        serde_yaml::from_str::<serde_yaml::Mapping>(&format!("{{{input_sets}}}"))?
-    } else {
-        serde_yaml::from_str("{}")?
-    };
-
-    let mut input_mapping = serde_yaml::Mapping::new();
-    input_file_yaml.into_iter().for_each(|(key, value)| { input_mapping.insert(key, value); });
-    input_sets_yaml.into_iter().for_each(|(key, value)| { input_mapping.insert(key, value); });
-
     let name = matches.get_one::<String>("name").ok_or(anyhow!("'name' must be provided"))?;
-    input_mapping.insert(serde_yaml::Value::String("name".to_string()), serde_yaml::Value::String(name.clone()));
+    let namespace = matches.get_one::<String>("namespace");
+    let input = input_from_matches(matches, name, namespace.map(String::as_str))?;
 
     let output_mode = match matches.get_one::<String>("output").map(|o| o.as_str()) {
         None | Some("yaml") => OutputMode::Yaml,
@@ -39,11 +23,12 @@ pub fn run_command(matches: &ArgMatches) -> anyhow::Result<()> {
         Some(o) => Err(anyhow!("Unexpected output type: {}", o))?,
     };
 
-    let input = serde_yaml::to_string(&MistInput { data: input_mapping })?;
+    let input = serde_yaml::to_string(&input)?;
     let mut instance  = MistPackageInstance::load(&MistPackageRef::from_str(&package)?)?;
+    note_resolved(package, &instance);
     let result = instance.generate(&input);
     
-    output_result(result, output_mode, name, process)?;
+    output_result(result, output_mode, name, namespace, process)?;
 
     Ok(())
 }
@@ -54,7 +39,7 @@ enum OutputMode {
     Dir(PathBuf),
 }
 
-fn output_result(result: MistResult, mode: OutputMode, name: &str, process: bool) -> anyhow::Result<()> {
+fn output_result(result: MistResult, mode: OutputMode, name: &str, namespace: Option<&String>, process: bool) -> anyhow::Result<()> {
     if let Ok(output) = &result {
         if let Some(message) = output.get_message() {
             println!("{}", message);
@@ -69,7 +54,7 @@ fn output_result(result: MistResult, mode: OutputMode, name: &str, process: bool
     match mode {
         OutputMode::Raw => Ok(println!("{}", result.mc_output_raw()?)),
         OutputMode::Yaml => match process {
-            true => Ok(println!("{}", result.mc_output_processed_yaml(name.to_string(), None)?)),
+            true => Ok(println!("{}", result.mc_output_processed_yaml(name.to_string(), None, namespace.cloned())?)),
             false => Ok(println!("{}", result.mc_output_yaml()?)),
         },
         OutputMode::Dir(path) => Ok(result.mc_output_dir(&path)?),
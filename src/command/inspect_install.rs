@@ -1,4 +1,4 @@
-use crate::installation::InstallRef;
+use crate::installation::{ClusterSession, InstallRef};
 use crate::outputs::*;
 
 use anyhow::anyhow;
@@ -14,8 +14,17 @@ pub async fn run_command(matches: &ArgMatches) -> anyhow::Result<()> {
         Some(o) => Err(anyhow!("Unexpected output type: {}", o))?,
     };
 
-    let install_ref = InstallRef { name: name.to_string(), version: None };
-    let resources = install_ref.get_resources().await?;
+    let version = matches.get_one::<u32>("revision").copied();
+
+    let session = ClusterSession::new().await?;
+    let install_ref = InstallRef { name: name.to_string(), version };
+    let resources = if matches.get_flag("repair-inventory") {
+        install_ref.repair_inventory(&session).await?
+    } else if matches.get_flag("scan") {
+        install_ref.scan_resources(&session).await?
+    } else {
+        install_ref.get_resources(&session).await?
+    };
 
     output_resources(resources, output_mode, name)?;
 
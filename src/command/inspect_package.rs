@@ -1,3 +1,4 @@
+use crate::command::install::note_resolved;
 use crate::instance::{MistPackageInstance, MistPackageRef};
 
 use clap::ArgMatches;
@@ -5,6 +6,7 @@ use clap::ArgMatches;
 pub fn run_command(matches: &ArgMatches) -> anyhow::Result<()> {
     let package = matches.get_one::<String>("package").unwrap();
     let mut instance = MistPackageInstance::load(&MistPackageRef::from_str(package)?)?;
+    note_resolved(package, &instance);
     println!("{}", serde_yaml::to_string(&instance.info()?)?.trim());
 
     Ok(())
@@ -1,16 +1,87 @@
-use crate::installation::{InstallResources, InstallRef};
+use crate::cluster;
+use crate::installation::{ApplyOptions, DEFAULT_FIELD_MANAGER, ClusterSession, DryRun, InstallResources, InstallRef, RolloutOptions, describe_unknown_kind};
 use crate::instance::{MistPackageInstance, MistPackageRef};
 
 use std::fs;
 use std::path::PathBuf;
+use std::time::Duration;
 
 use anyhow::anyhow;
 use clap::ArgMatches;
-use mistletoe_api::v1alpha1::MistInput;
+use colored::Colorize;
+use kube::core::DynamicObject;
+use mistletoe_api::v1alpha1::{MistInput, MistOutput};
 
 pub async fn run_command(matches: &ArgMatches) -> anyhow::Result<()> {
     let package = matches.get_one::<String>("package").unwrap();
+    let name = matches.get_one::<String>("name").ok_or(anyhow!("'name' must be provided"))?;
+    let namespace = matches.get_one::<String>("namespace");
+
+    let input = input_from_matches(matches, name, namespace.map(String::as_str))?;
+    let mut instance  = MistPackageInstance::load(&MistPackageRef::from_str(&package)?)?;
+    let package = &note_resolved(package, &instance);
+    let output = instance.generate(&serde_yaml::to_string(&input)?)?;
+
+    if let Some(message) = output.get_message() {
+        println!("{}", message);
+    }
+
+    let session = ClusterSession::new().await?;
+    let dry_run = dry_run_from_matches(matches)?;
+
+    // The namespace is created unlabeled, so it's noted for the ownership check to let the
+    // package's own Namespace take it over.
+    let created_namespace = match (namespace, matches.get_flag("create-namespace")) {
+        (Some(namespace), true) if dry_run == DryRun::None => {
+            let created = cluster::ensure_namespace(session.client(), namespace).await?;
+            if created {
+                println!("created namespace \"{}\"", namespace);
+            }
+            created.then(|| namespace.clone())
+        },
+        (Some(namespace), true) => (!cluster::namespace_exists(session.client(), namespace).await?)
+            .then(|| namespace.clone()),
+        _ => None,
+    };
+
+    let options = RolloutOptions {
+        apply: ApplyOptions {
+            created_namespace,
+            ..apply_options_from_matches(matches, &session, name).await?
+        },
+        wait: wait_from_matches(matches)?,
+        atomic: atomic_from_matches(matches),
+        ..RolloutOptions::default()
+    };
+
+    let summary = InstallRef { name: name.to_string(), version: None }
+        .rollout(&session, package, input, resources_from_output(&output, namespace.map(String::as_str))?, &options).await?;
+
+    warn_skipped(&summary.skipped);
+
+    match dry_run {
+        DryRun::None => println!("installed \"{}\" at revision {}", name, summary.version),
+        DryRun::Client => println!("{}", summary.applied.to_string()?),
+        DryRun::Server => println!("installed \"{}\" at revision {} (server dry run)", name, summary.version),
+    }
+    
+    Ok(())
+}
+
+/// Parses the rendered resources, placing namespaced resources without a namespace in the
+/// target namespace if one was given.
+pub(crate) fn resources_from_output(output: &MistOutput, namespace: Option<&str>) -> anyhow::Result<InstallResources> {
+    let resources = InstallResources::from_output(output)?;
 
+    Ok(match namespace {
+        Some(namespace) => resources.with_default_namespace(namespace),
+        None => resources,
+    })
+}
+
+/// Builds the package input from the `--inputfile` and `--set` arguments, with the
+/// installation name and target namespace set on top.
+pub(crate) fn input_from_matches(matches: &ArgMatches, name: &str, namespace: Option<&str>) -> anyhow::Result<MistInput> {
     let input_file_yaml = if let Some(input_file) = matches.get_one::<PathBuf>("inputfile") {
         let input_file_string = String::from_utf8(fs::read(input_file)?)?;
         serde_yaml::from_str::<serde_yaml::Mapping>(&input_file_string)?
@@ -27,23 +98,136 @@ pub async fn run_command(matches: &ArgMatches) -> anyhow::Result<()> {
     let mut input_mapping = serde_yaml::Mapping::new();
     input_file_yaml.into_iter().for_each(|(key, value)| { input_mapping.insert(key, value); });
     input_sets_yaml.into_iter().for_each(|(key, value)| { input_mapping.insert(key, value); });
+    input_mapping.insert(serde_yaml::Value::String("name".to_string()), serde_yaml::Value::String(name.to_string()));
 
-    let name = matches.get_one::<String>("name").ok_or(anyhow!("'name' must be provided"))?;
-    input_mapping.insert(serde_yaml::Value::String("name".to_string()), serde_yaml::Value::String(name.clone()));
+    if let Some(namespace) = namespace {
+        input_mapping.insert(serde_yaml::Value::String("namespace".to_string()), serde_yaml::Value::String(namespace.to_string()));
+    }
 
-    let input = serde_yaml::to_string(&MistInput { data: input_mapping })?;
-    let mut instance  = MistPackageInstance::load(&MistPackageRef::from_str(&package)?)?;
-    let output = instance.generate(&input)?;
+    Ok(MistInput { data: input_mapping })
+}
 
-    if let Some(message) = output.get_message() {
-        println!("{}", message);
+/// Returns the target namespace: `--namespace` if given, otherwise the one the latest revision of
+/// the installation was rendered for, so rolling it out again doesn't move it.
+pub(crate) async fn namespace_from_matches(matches: &ArgMatches, session: &ClusterSession, name: &str)
+    -> anyhow::Result<Option<String>>
+{
+    if let Some(namespace) = matches.get_one::<String>("namespace") {
+        return Ok(Some(namespace.clone()));
     }
 
-    for (_, content) in output.get_files() {
-        InstallRef { name: name.to_string(), version: None}
-            .apply_resources(&InstallResources::from_str(content)?
-                .label_resources_with(name, None)?).await?;
+    let latest = InstallRef { name: name.to_string(), version: None }
+        .get_record(session).await?;
+
+    Ok(latest.and_then(|record| record.input.data.get("namespace")
+        .and_then(|namespace| namespace.as_str())
+        .map(str::to_string)))
+}
+
+/// Builds the apply options from the arguments.  The field manager and conflict forcing stay with
+/// the installation: unless `--field-manager` or `--force-conflicts` are given, the ones its latest
+/// revision was recorded with are used, so every machine rolls it out the same way.
+pub(crate) async fn apply_options_from_matches(matches: &ArgMatches, session: &ClusterSession, name: &str)
+    -> anyhow::Result<ApplyOptions>
+{
+    let latest = InstallRef { name: name.to_string(), version: None }
+        .get_record(session).await?;
+
+    let field_manager = matches.try_get_one::<String>("field-manager").ok().flatten().cloned()
+        .or_else(|| latest.as_ref().and_then(|record| record.field_manager.clone()))
+        .unwrap_or(DEFAULT_FIELD_MANAGER.to_string());
+    let force_conflicts = matches.try_get_one::<bool>("force-conflicts").ok().flatten().copied()
+        .or_else(|| latest.as_ref().and_then(|record| record.force_conflicts))
+        .unwrap_or(true);
+
+    Ok(ApplyOptions {
+        dry_run: dry_run_from_matches(matches)?,
+        skip_unknown_kinds: matches.try_get_one::<bool>("skip-unknown-kinds").ok().flatten().copied().unwrap_or_default(),
+        adopt: matches.try_get_one::<bool>("adopt").ok().flatten().copied().unwrap_or_default(),
+        field_manager,
+        force_conflicts,
+        created_namespace: None,
+    })
+}
+
+/// Returns the `--dry-run` mode, for commands that take it.
+pub(crate) fn dry_run_from_matches(matches: &ArgMatches) -> anyhow::Result<DryRun> {
+    match matches.try_get_one::<String>("dry-run").ok().flatten() {
+        Some(dry_run) => DryRun::from_str(dry_run),
+        None => Ok(DryRun::None),
+    }
+}
+
+/// Returns the timeout to wait for resources to become ready with if `--wait` or `--atomic` was
+/// given, defaulting to 5 minutes.
+pub(crate) fn wait_from_matches(matches: &ArgMatches) -> anyhow::Result<Option<Duration>> {
+    if !matches.get_flag("wait") && !atomic_from_matches(matches) {
+        return Ok(None);
+    }
+
+    match matches.get_one::<String>("timeout") {
+        Some(timeout) => Ok(Some(parse_duration(timeout)?)),
+        None => Ok(Some(Duration::from_secs(300))),
+    }
+}
+
+/// Whether `--atomic` was given, for commands that support it.
+pub(crate) fn atomic_from_matches(matches: &ArgMatches) -> bool {
+    matches.try_get_one::<bool>("atomic").ok().flatten().copied().unwrap_or_default()
+}
+
+/// Parses durations like `90`, `90s`, `5m` or `1h30m`, where plain numbers are seconds.
+fn parse_duration(duration: &str) -> anyhow::Result<Duration> {
+    let mut seconds = 0;
+    let mut number = String::new();
+
+    for c in duration.trim().chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            _ => return Err(anyhow!("unexpected unit '{}' in duration \"{}\"", c, duration)),
+        };
+
+        seconds += number.parse::<u64>()
+            .map_err(|_| anyhow!("expected a number before '{}' in duration \"{}\"", c, duration))? * unit;
+        number.clear();
+    }
+
+    if !number.is_empty() {
+        seconds += number.parse::<u64>()?;
+    }
+
+    Ok(Duration::from_secs(seconds))
+}
+
+/// Tells the user which exact version a version range or `latest` resolved to, and returns the
+/// resolved package reference to record.
+pub(crate) fn note_resolved(package: &str, instance: &MistPackageInstance) -> String {
+    let resolved = instance.package_ref().to_string();
+
+    if resolved != package {
+        eprintln!("resolved {} to {}", package, resolved);
+    }
+
+    resolved
+}
+
+/// Prints a warning listing the resources that were skipped because the cluster doesn't serve
+/// their kind.
+pub(crate) fn warn_skipped(skipped: &[DynamicObject]) {
+    if skipped.is_empty() {
+        return;
+    }
+
+    eprintln!("{}{} skipped {} resource(s) of kinds the cluster doesn't serve:",
+        "warning".bold().yellow(), ":".bold(), skipped.len());
+    for resource in skipped {
+        eprintln!("  {}", describe_unknown_kind(resource));
     }
-    
-    Ok(())
 }
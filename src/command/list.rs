@@ -0,0 +1,34 @@
+use crate::installation::{ClusterSession, InstallSummary, list_installs};
+use crate::outputs::*;
+
+use anyhow::anyhow;
+use clap::ArgMatches;
+
+pub async fn run_command(matches: &ArgMatches) -> anyhow::Result<()> {
+    let output_mode = match matches.get_one::<String>("output").map(|o| o.as_str()) {
+        None | Some("table") => OutputMode::Table,
+        Some("yaml") => OutputMode::Yaml,
+        Some("json") => OutputMode::Json,
+        Some(o) => Err(anyhow!("Unexpected output type: {}", o))?,
+    };
+
+    let session = ClusterSession::new().await?;
+    let installs = list_installs(&session, matches.get_flag("scan")).await?;
+    output_installs(installs, output_mode)?;
+
+    Ok(())
+}
+
+enum OutputMode {
+    Table,
+    Yaml,
+    Json,
+}
+
+fn output_installs(installs: Vec<InstallSummary>, mode: OutputMode) -> anyhow::Result<()> {
+    match mode {
+        OutputMode::Table => Ok(println!("{}", installs.mc_output_table()?)),
+        OutputMode::Yaml => Ok(println!("{}", installs.mc_output_yaml()?)),
+        OutputMode::Json => Ok(println!("{}", installs.mc_output_json()?)),
+    }
+}
@@ -1,8 +1,16 @@
+pub mod diff;
+pub mod drift;
 pub mod generate;
 pub mod inspect_install;
 pub mod inspect_package;
 pub mod install;
+pub mod list;
 pub mod registry_add;
+pub mod registry_index;
 pub mod registry_list;
 pub mod registry_remove;
+pub mod rollback;
+pub mod search;
+pub mod status;
 pub mod uninstall;
+pub mod upgrade;
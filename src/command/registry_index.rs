@@ -0,0 +1,20 @@
+use crate::registry::{RegistryIndex, REGISTRY_INDEX_FILE};
+
+use std::path::PathBuf;
+
+use clap::ArgMatches;
+
+pub fn run_command(matches: &ArgMatches) -> anyhow::Result<()> {
+    let root = matches.get_one::<PathBuf>("path").cloned().unwrap_or(PathBuf::from("."));
+    let index = RegistryIndex::generate(&root)?;
+
+    if matches.get_flag("stdout") {
+        print!("{}", index.to_string()?);
+        return Ok(());
+    }
+
+    index.write_to_dir(&root)?;
+    println!("indexed {} package(s) into {}", index.packages.len(), root.join(REGISTRY_INDEX_FILE).display());
+
+    Ok(())
+}
@@ -0,0 +1,57 @@
+use crate::command::install::{apply_options_from_matches, wait_from_matches, warn_skipped};
+use crate::installation::{ClusterSession, InstallResources, InstallRef, RolloutOptions, InstallStatus};
+use crate::outputs::*;
+
+use anyhow::anyhow;
+use clap::ArgMatches;
+
+pub async fn run_command(matches: &ArgMatches) -> anyhow::Result<()> {
+    let name = matches.get_one::<String>("name").unwrap();
+    let revision = matches.get_one::<u32>("revision").copied();
+
+    let session = ClusterSession::new().await?;
+    let install_ref = InstallRef { name: name.to_string(), version: None };
+    let records = install_ref.get_records(&session).await?;
+    let latest = records.last()
+        .ok_or(anyhow!("no installation found with the name \"{}\"", name))?;
+
+    // Without an explicit revision, roll back to the newest one before the latest that was
+    // actually deployed.
+    let target = match revision {
+        Some(revision) => records.iter()
+            .find(|record| record.version == revision)
+            .ok_or(anyhow!("installation \"{}\" has no revision {}", name, revision))?,
+        None => records.iter().rev()
+            .filter(|record| record.version < latest.version)
+            .find(|record| matches!(record.status, InstallStatus::Deployed | InstallStatus::Superseded))
+            .ok_or(anyhow!("installation \"{}\" has no previous deployed revision to roll back to", name))?,
+    };
+
+    let options = RolloutOptions {
+        prune: true,
+        upgrade: true,
+        apply: apply_options_from_matches(matches, &session, name).await?,
+        wait: wait_from_matches(matches)?,
+        ..RolloutOptions::default()
+    };
+    let summary = install_ref.rollout(
+        &session,
+        &target.package,
+        target.input.clone(),
+        InstallResources::from_str(&target.manifest)?,
+        &options).await?;
+
+    warn_skipped(&summary.skipped);
+
+    if !summary.pruned.is_empty() {
+        println!("{}", summary.pruned.mc_output_list()?
+            .lines()
+            .map(|line| format!("pruned {}", line))
+            .collect::<Vec<String>>()
+            .join("\n"));
+    }
+
+    println!("rolled back \"{}\" to revision {} as revision {}", name, target.version, summary.version);
+
+    Ok(())
+}
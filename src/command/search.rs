@@ -0,0 +1,63 @@
+use crate::config::ConfigLayout;
+use crate::outputs::*;
+use crate::registry::{Remote, SearchResult};
+
+use anyhow::anyhow;
+use clap::ArgMatches;
+use colored::Colorize;
+
+pub fn run_command(matches: &ArgMatches) -> anyhow::Result<()> {
+    let keyword = matches.get_one::<String>("keyword").map(String::as_str);
+    let labels = matches.get_many::<String>("label")
+        .map(|labels| labels.map(|label| parse_label(label)).collect::<anyhow::Result<Vec<_>>>())
+        .transpose()?
+        .unwrap_or_default();
+
+    let output_mode = match matches.get_one::<String>("output").map(|o| o.as_str()) {
+        None | Some("table") => OutputMode::Table,
+        Some("yaml") => OutputMode::Yaml,
+        Some("json") => OutputMode::Json,
+        Some(o) => Err(anyhow!("Unexpected output type: {}", o))?,
+    };
+
+    let config = ConfigLayout::from_env()?;
+    let mut results = Vec::new();
+
+    for registry in &config.spec.registries {
+        let remote = Remote::default_for_name(&registry.name, &config)?;
+        remote.init()?;
+
+        // A registry that can't be reached is still searched as of its last pull.
+        if let Err(e) = remote.pull() {
+            eprintln!("{}{} couldn't update registry \"{}\": {}",
+                "warning".bold().yellow(), ":".bold(), registry.name, e);
+        }
+
+        results.extend(remote.index()?.packages.iter()
+            .filter(|package| package.matches(keyword, &labels))
+            .map(|package| SearchResult::new(&registry.name, package)));
+    }
+
+    output_results(results, output_mode)
+}
+
+enum OutputMode {
+    Table,
+    Yaml,
+    Json,
+}
+
+/// Parses a `--label` selector of the form `<key>=<value>`.
+fn parse_label(label: &str) -> anyhow::Result<(String, String)> {
+    label.split_once('=')
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .ok_or_else(|| anyhow!("expected a label in the form `<key>=<value>`, got \"{}\"", label))
+}
+
+fn output_results(results: Vec<SearchResult>, mode: OutputMode) -> anyhow::Result<()> {
+    match mode {
+        OutputMode::Table => Ok(println!("{}", results.mc_output_table()?)),
+        OutputMode::Yaml => Ok(println!("{}", results.mc_output_yaml()?)),
+        OutputMode::Json => Ok(println!("{}", results.mc_output_json()?)),
+    }
+}
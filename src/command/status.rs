@@ -0,0 +1,47 @@
+use crate::installation::{ClusterSession, InstallHealth, InstallRef, Verdict};
+use crate::outputs::*;
+
+use anyhow::anyhow;
+use clap::ArgMatches;
+
+pub async fn run_command(matches: &ArgMatches) -> anyhow::Result<()> {
+    let name = matches.get_one::<String>("name").unwrap();
+
+    let output_mode = match matches.get_one::<String>("output").map(|o| o.as_str()) {
+        None | Some("table") => OutputMode::Table,
+        Some("yaml") => OutputMode::Yaml,
+        Some("json") => OutputMode::Json,
+        Some(o) => Err(anyhow!("Unexpected output type: {}", o))?,
+    };
+
+    let session = ClusterSession::new().await?;
+    let status = InstallRef { name: name.to_string(), version: None }
+        .get_status(&session).await?;
+
+    if status.resources.is_empty() {
+        return Err(anyhow!("no resources found for installation name \"{}\"", name));
+    }
+
+    output_status(&status, output_mode)?;
+
+    // Anything short of healthy fails the command, so deploy scripts can gate on it.
+    match status.verdict {
+        Verdict::Healthy => Ok(()),
+        verdict => Err(anyhow!("installation \"{}\" is {}", name, verdict)),
+    }
+}
+
+enum OutputMode {
+    Table,
+    Yaml,
+    Json,
+}
+
+fn output_status(status: &InstallHealth, mode: OutputMode) -> anyhow::Result<()> {
+    match mode {
+        OutputMode::Table => Ok(println!("{}\n\ninstallation \"{}\" is {}",
+            status.mc_output_table()?, status.name, status.verdict)),
+        OutputMode::Yaml => Ok(println!("{}", status.mc_output_yaml()?)),
+        OutputMode::Json => Ok(println!("{}", status.mc_output_json()?)),
+    }
+}
@@ -1,4 +1,5 @@
-use crate::installation::InstallRef;
+use crate::command::install::{apply_options_from_matches, dry_run_from_matches, wait_from_matches};
+use crate::installation::{Cascade, ClusterSession, DEFAULT_HOOK_TIMEOUT, DeleteOptions, DeleteSummary, DryRun, HookPhase, InstallRef};
 use crate::outputs::*;
 
 use anyhow::anyhow;
@@ -8,21 +9,89 @@ use kube::core::DynamicObject;
 pub async fn run_command(matches: &ArgMatches) -> anyhow::Result<()> {
     let name = matches.get_one::<String>("name").unwrap();
 
+    let options = DeleteOptions {
+        dry_run: dry_run_from_matches(matches)?,
+        cascade: match matches.get_one::<String>("cascade") {
+            Some(cascade) => Cascade::from_str(cascade)?,
+            None => Cascade::default(),
+        },
+        keep_kinds: matches.get_many::<String>("keep-kind")
+            .map(|kinds| kinds.cloned().collect())
+            .unwrap_or_default(),
+        wait: wait_from_matches(matches)?,
+    };
+
+    let session = ClusterSession::new().await?;
     let install_ref = InstallRef { name: name.to_string(), version: None };
-    let resources = install_ref.delete_resources().await?;
-    output_resources(resources, name)?;
+    let resources = if matches.get_flag("scan") {
+        install_ref.scan_resources(&session).await?
+    } else {
+        install_ref.get_resources_in(&session, matches.get_one::<String>("namespace").map(String::as_str)).await?
+    };
+
+    // An installation whose resources are all gone can still have records left over, which are
+    // removed without running any hooks.
+    if resources.is_empty() {
+        if install_ref.get_records(&session).await?.is_empty() {
+            return Err(anyhow!("no resources found for installation name \"{}\"", name));
+        }
+
+        match options.dry_run {
+            DryRun::None => {
+                install_ref.delete_records(&session).await?;
+                println!("removed the records of installation \"{}\", which had no resources left", name);
+            },
+            _ => println!("would remove the records of installation \"{}\", which has no resources left", name),
+        }
+
+        return Ok(());
+    }
+
+    let hooks = install_ref.get_hooks(&session).await?;
+    let hook_options = apply_options_from_matches(matches, &session, name).await?;
+    let hook_timeout = options.wait.unwrap_or(DEFAULT_HOOK_TIMEOUT);
+
+    let ran = install_ref.run_hooks(&session, &hooks, HookPhase::PreDelete, &hook_options, hook_timeout).await?;
+    output_hooks(ran, HookPhase::PreDelete)?;
+
+    let summary = install_ref.delete_objects(&session, &resources, &options).await?;
+    output_summary(summary, options.dry_run)?;
+
+    if options.dry_run == DryRun::None {
+        install_ref.delete_records(&session).await?;
+    }
+
+    let ran = install_ref.run_hooks(&session, &hooks, HookPhase::PostDelete, &hook_options, hook_timeout).await?;
+    output_hooks(ran, HookPhase::PostDelete)?;
 
     Ok(())
 }
 
-fn output_resources(resources: Vec<DynamicObject>, name: &str) -> anyhow::Result<()> {
-    if resources.is_empty() {
-        return Err(anyhow!("no resources found for installation name \"{}\"", name));
+fn output_hooks(ran: Vec<DynamicObject>, phase: HookPhase) -> anyhow::Result<()> {
+    for line in (&ran).mc_output_list()?.lines() {
+        println!("ran {} hook {}", phase, line);
     }
 
-    Ok(println!("{}", resources.mc_output_list()?
-        .lines().into_iter()
-        .map(|line| format!("deleted {}", line))
+    Ok(())
+}
+
+fn output_summary(summary: DeleteSummary, dry_run: DryRun) -> anyhow::Result<()> {
+    let deleted_lines = summary.deleted.mc_output_list()?
+        .lines()
+        .map(|line| match dry_run {
+            DryRun::None => format!("deleted {}", line),
+            DryRun::Client => format!("would delete {}", line),
+            DryRun::Server => format!("deleted {} (server dry run)", line),
+        })
+        .collect::<Vec<String>>();
+
+    let kept_lines = summary.kept.mc_output_list()?
+        .lines()
+        .map(|line| format!("kept {}", line))
+        .collect::<Vec<String>>();
+
+    Ok(println!("{}", deleted_lines.into_iter()
+        .chain(kept_lines)
         .collect::<Vec<String>>()
         .join("\n")))
 }
@@ -0,0 +1,51 @@
+use crate::command::install::{apply_options_from_matches, atomic_from_matches, input_from_matches, namespace_from_matches, note_resolved, resources_from_output, wait_from_matches, warn_skipped};
+use crate::installation::{ClusterSession, InstallRef, RolloutOptions};
+use crate::instance::{MistPackageInstance, MistPackageRef};
+use crate::outputs::*;
+
+use anyhow::anyhow;
+use clap::ArgMatches;
+
+pub async fn run_command(matches: &ArgMatches) -> anyhow::Result<()> {
+    let package = matches.get_one::<String>("package").unwrap();
+    let name = matches.get_one::<String>("name").ok_or(anyhow!("'name' must be provided"))?;
+
+    let session = ClusterSession::new().await?;
+    let install_ref = InstallRef { name: name.to_string(), version: None };
+    let previous_version = install_ref.get_latest_version(&session).await?
+        .ok_or(anyhow!("no installation found with the name \"{}\", use `install` instead", name))?;
+
+    let namespace = namespace_from_matches(matches, &session, name).await?;
+    let input = input_from_matches(matches, name, namespace.as_deref())?;
+    let mut instance  = MistPackageInstance::load(&MistPackageRef::from_str(package)?)?;
+    let package = &note_resolved(package, &instance);
+    let output = instance.generate(&serde_yaml::to_string(&input)?)?;
+
+    if let Some(message) = output.get_message() {
+        println!("{}", message);
+    }
+
+    let options = RolloutOptions {
+        prune: true,
+        upgrade: true,
+        apply: apply_options_from_matches(matches, &session, name).await?,
+        wait: wait_from_matches(matches)?,
+        atomic: atomic_from_matches(matches),
+    };
+    let summary = install_ref
+        .rollout(&session, package, input, resources_from_output(&output, namespace.as_deref())?, &options).await?;
+
+    warn_skipped(&summary.skipped);
+
+    if !summary.pruned.is_empty() {
+        println!("{}", summary.pruned.mc_output_list()?
+            .lines()
+            .map(|line| format!("pruned {}", line))
+            .collect::<Vec<String>>()
+            .join("\n"));
+    }
+
+    println!("upgraded \"{}\" from revision {} to revision {}", name, previous_version, summary.version);
+
+    Ok(())
+}
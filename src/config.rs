@@ -39,6 +39,8 @@ impl ConfigLayout {
             kind: KIND.to_string(),
             spec: SpecLayout {
                 registries: Vec::new(),
+                default_context: None,
+                discovery_cache_ttl: None,
             },
         }
     }
@@ -96,8 +98,16 @@ impl ConfigLayout {
 }
 
 #[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct SpecLayout {
     pub registries: Vec<RegistryLayout>,
+    /// Kubeconfig context to use when `--context` isn't given, instead of the current context.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_context: Option<String>,
+    /// How many seconds to cache the cluster's discovery results on disk for.  Discovery isn't
+    /// cached on disk if this isn't set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub discovery_cache_ttl: Option<u64>,
 }
 
 impl SpecLayout {
@@ -0,0 +1,286 @@
+use k8s_openapi::api::core::v1::Endpoints;
+use kube::core::DynamicObject;
+use serde::Serialize;
+use serde_json::Value;
+
+/// Health of a single live resource.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize)]
+#[serde(tag = "state", content = "reason")]
+pub enum Health {
+    /// The resource is up and doing what it should.
+    Ready,
+    /// The resource isn't ready yet, with the reason why.
+    Progressing(String),
+    /// The resource has failed in a way that waiting won't fix, with the reason why.
+    Failed(String),
+}
+
+impl Health {
+    pub fn is_ready(&self) -> bool {
+        *self == Health::Ready
+    }
+
+    pub fn reason(&self) -> Option<&str> {
+        match self {
+            Health::Ready => None,
+            Health::Progressing(reason) | Health::Failed(reason) => Some(reason),
+        }
+    }
+}
+
+/// Evaluates the health of a live resource from its status.
+///
+/// Workloads have to be rolled out, Jobs completed, PersistentVolumeClaims bound and
+/// CustomResourceDefinitions established.  Anything else is judged by its `Ready` condition if
+/// it has one, and is otherwise considered ready as soon as it exists.  Services depend on their
+/// Endpoints, so they're evaluated with [service_health] instead.
+pub fn resource_health(resource: &DynamicObject) -> Health {
+    let kind = resource.types.as_ref().map(|types| types.kind.as_str()).unwrap_or_default();
+    let data = &resource.data;
+
+    if !observed_latest_generation(resource) {
+        return Health::Progressing("the controller hasn't observed the latest changes yet".to_string());
+    }
+
+    match kind {
+        "Deployment" => deployment_health(data),
+        "StatefulSet" => stateful_set_health(data),
+        "DaemonSet" => daemon_set_health(data),
+        "Job" => job_health(data),
+        "Pod" => pod_health(data),
+        "PersistentVolumeClaim" => match data["status"]["phase"].as_str() {
+            Some("Bound") => Health::Ready,
+            Some("Lost") => Health::Failed("the claim lost its volume".to_string()),
+            phase => Health::Progressing(format!("claim is {}", phase.unwrap_or("not bound"))),
+        },
+        "CustomResourceDefinition" => match condition_status(data, "Established") {
+            Some("True") => Health::Ready,
+            _ => Health::Progressing("the definition isn't established yet".to_string()),
+        },
+        _ => match condition_status(data, "Ready") {
+            Some("True") | None => Health::Ready,
+            Some(_) => Health::Progressing(condition_message(data, "Ready")
+                .unwrap_or("the Ready condition isn't true".to_string())),
+        },
+    }
+}
+
+/// Evaluates whether a hook has run to completion.  This is [resource_health], except that a
+/// running Pod isn't done until it has succeeded.
+pub fn hook_health(resource: &DynamicObject) -> Health {
+    let kind = resource.types.as_ref().map(|types| types.kind.as_str()).unwrap_or_default();
+
+    if kind != "Pod" {
+        return resource_health(resource);
+    }
+
+    match resource.data["status"]["phase"].as_str() {
+        Some("Succeeded" | "Failed") => pod_health(&resource.data),
+        phase => Health::Progressing(format!("pod is {}", phase.unwrap_or("Pending"))),
+    }
+}
+
+/// Evaluates the health of a live Service given its Endpoints, if any exist.
+pub fn service_health(service: &DynamicObject, endpoints: Option<&Endpoints>) -> Health {
+    let spec = &service.data["spec"];
+
+    // ExternalName Services and Services without a selector don't get managed endpoints.
+    if spec["type"].as_str() == Some("ExternalName") || spec["selector"].as_object().is_none_or(|s| s.is_empty()) {
+        return Health::Ready;
+    }
+
+    let has_addresses = endpoints
+        .and_then(|endpoints| endpoints.subsets.as_ref())
+        .is_some_and(|subsets| subsets.iter()
+            .any(|subset| subset.addresses.as_ref().is_some_and(|addresses| !addresses.is_empty())));
+
+    if has_addresses {
+        Health::Ready
+    } else {
+        Health::Progressing("the service has no ready endpoints".to_string())
+    }
+}
+
+fn observed_latest_generation(resource: &DynamicObject) -> bool {
+    match (resource.metadata.generation, resource.data["status"]["observedGeneration"].as_i64()) {
+        (Some(generation), Some(observed)) => observed >= generation,
+        _ => true,
+    }
+}
+
+fn deployment_health(data: &Value) -> Health {
+    let status = &data["status"];
+
+    if condition_reason(data, "Progressing") == Some("ProgressDeadlineExceeded") {
+        return Health::Failed(format!("the rollout exceeded its progress deadline: {}",
+            condition_message(data, "Progressing").unwrap_or_default()));
+    }
+
+    let replicas = data["spec"]["replicas"].as_i64().unwrap_or(1);
+    let updated = status["updatedReplicas"].as_i64().unwrap_or(0);
+    let available = status["availableReplicas"].as_i64().unwrap_or(0);
+    let total = status["replicas"].as_i64().unwrap_or(0);
+
+    if updated < replicas {
+        Health::Progressing(format!("{} of {} replicas updated", updated, replicas))
+    } else if total > updated {
+        Health::Progressing(format!("{} old replicas pending termination", total - updated))
+    } else if available < replicas {
+        Health::Progressing(format!("{} of {} replicas available", available, replicas))
+    } else {
+        Health::Ready
+    }
+}
+
+fn stateful_set_health(data: &Value) -> Health {
+    let status = &data["status"];
+    let replicas = data["spec"]["replicas"].as_i64().unwrap_or(1);
+    let ready = status["readyReplicas"].as_i64().unwrap_or(0);
+    let updated = status["updatedReplicas"].as_i64().unwrap_or(0);
+
+    let rolling_update = data["spec"]["updateStrategy"]["type"].as_str().unwrap_or("RollingUpdate") == "RollingUpdate";
+
+    if rolling_update && updated < replicas {
+        Health::Progressing(format!("{} of {} replicas updated", updated, replicas))
+    } else if ready < replicas {
+        Health::Progressing(format!("{} of {} replicas ready", ready, replicas))
+    } else {
+        Health::Ready
+    }
+}
+
+fn daemon_set_health(data: &Value) -> Health {
+    let status = &data["status"];
+    let desired = status["desiredNumberScheduled"].as_i64().unwrap_or(0);
+    let updated = status["updatedNumberScheduled"].as_i64().unwrap_or(0);
+    let available = status["numberAvailable"].as_i64().unwrap_or(0);
+
+    if updated < desired {
+        Health::Progressing(format!("{} of {} pods updated", updated, desired))
+    } else if available < desired {
+        Health::Progressing(format!("{} of {} pods available", available, desired))
+    } else {
+        Health::Ready
+    }
+}
+
+fn job_health(data: &Value) -> Health {
+    if condition_status(data, "Complete") == Some("True") {
+        Health::Ready
+    } else if condition_status(data, "Failed") == Some("True") {
+        Health::Failed(condition_message(data, "Failed").unwrap_or("the job failed".to_string()))
+    } else {
+        Health::Progressing(format!("{} pods succeeded",
+            data["status"]["succeeded"].as_i64().unwrap_or(0)))
+    }
+}
+
+fn pod_health(data: &Value) -> Health {
+    match data["status"]["phase"].as_str() {
+        Some("Succeeded") => Health::Ready,
+        Some("Failed") => Health::Failed(data["status"]["message"].as_str()
+            .unwrap_or("the pod failed").to_string()),
+        Some("Running") if condition_status(data, "Ready") == Some("True") => Health::Ready,
+        phase => Health::Progressing(format!("pod is {}", phase.unwrap_or("Pending"))),
+    }
+}
+
+fn condition<'a>(data: &'a Value, type_: &str) -> Option<&'a Value> {
+    data["status"]["conditions"].as_array()?.iter()
+        .find(|condition| condition["type"].as_str() == Some(type_))
+}
+
+fn condition_status<'a>(data: &'a Value, type_: &str) -> Option<&'a str> {
+    condition(data, type_)?["status"].as_str()
+}
+
+fn condition_reason<'a>(data: &'a Value, type_: &str) -> Option<&'a str> {
+    condition(data, type_)?["reason"].as_str()
+}
+
+fn condition_message(data: &Value, type_: &str) -> Option<String> {
+    condition(data, type_)?["message"].as_str().map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object(value: Value) -> DynamicObject {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_deployment_health() {
+        let mut deployment = serde_json::json!({
+            "apiVersion": "apps/v1",
+            "kind": "Deployment",
+            "metadata": { "name": "my-app", "generation": 2 },
+            "spec": { "replicas": 3 },
+            "status": { "observedGeneration": 2, "replicas": 3, "updatedReplicas": 3, "availableReplicas": 1 },
+        });
+        assert_eq!(Health::Progressing("1 of 3 replicas available".to_string()),
+            resource_health(&object(deployment.clone())));
+
+        deployment["status"]["availableReplicas"] = 3.into();
+        assert_eq!(Health::Ready, resource_health(&object(deployment.clone())));
+
+        deployment["metadata"]["generation"] = 3.into();
+        assert!(!resource_health(&object(deployment)).is_ready());
+    }
+
+    #[test]
+    fn test_job_health() {
+        let job = object(serde_json::json!({
+            "apiVersion": "batch/v1",
+            "kind": "Job",
+            "metadata": { "name": "migrate" },
+            "status": { "conditions": [
+                { "type": "Failed", "status": "True", "message": "BackoffLimitExceeded" },
+            ]},
+        }));
+        assert_eq!(Health::Failed("BackoffLimitExceeded".to_string()), resource_health(&job));
+    }
+
+    #[test]
+    fn test_hook_health() {
+        let pod = object(serde_json::json!({
+            "apiVersion": "v1",
+            "kind": "Pod",
+            "metadata": { "name": "migrate" },
+            "status": {
+                "phase": "Running",
+                "conditions": [{ "type": "Ready", "status": "True" }],
+            },
+        }));
+        assert_eq!(Health::Ready, resource_health(&pod));
+        assert_eq!(Health::Progressing("pod is Running".to_string()), hook_health(&pod));
+    }
+
+    #[test]
+    fn test_generic_health() {
+        let config_map = object(serde_json::json!({
+            "apiVersion": "v1",
+            "kind": "ConfigMap",
+            "metadata": { "name": "my-config" },
+        }));
+        assert_eq!(Health::Ready, resource_health(&config_map));
+
+        let custom = object(serde_json::json!({
+            "apiVersion": "example.com/v1",
+            "kind": "Database",
+            "metadata": { "name": "my-db" },
+            "status": { "conditions": [
+                { "type": "Ready", "status": "False", "message": "provisioning" },
+            ]},
+        }));
+        assert_eq!(Health::Progressing("provisioning".to_string()), resource_health(&custom));
+    }
+
+    #[test]
+    fn test_health_serialization() {
+        assert_eq!(serde_json::json!({ "state": "Ready" }), serde_json::to_value(Health::Ready).unwrap());
+        assert_eq!(serde_json::json!({ "state": "Failed", "reason": "the job failed" }),
+            serde_json::to_value(Health::Failed("the job failed".to_string())).unwrap());
+    }
+}
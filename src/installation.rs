@@ -1,117 +1,709 @@
-use std::collections::BTreeMap;
+use crate::cluster;
+use crate::config::MIST_HOME_LOCATION;
+use crate::health::{Health, hook_health, resource_health, service_health};
+use crate::inventory::{Inventory, InventoryEntry};
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use anyhow::anyhow;
-use k8s_openapi::api::core::v1::Namespace;
+use futures::{StreamExt, TryStreamExt, stream};
+use k8s_openapi::ByteString;
+use k8s_openapi::api::core::v1::{Endpoints, Event, Secret};
+use k8s_openapi::chrono::{self, Utc};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::Time;
 use kube::{Client, Discovery, Api, ResourceExt};
-use kube::api::{ListParams, PatchParams, Patch, DeleteParams};
-use kube::core::{DynamicObject, GroupVersionKind};
+use kube::api::{ListParams, PatchParams, Patch, PostParams, DeleteParams, ObjectMeta, PropagationPolicy};
+use kube::core::{DynamicObject, GroupVersionKind, TypeMeta};
 use kube::discovery::{ApiResource, ApiCapabilities, Scope};
-use serde_yaml::Mapping;
+use mistletoe_api::v1alpha1::{MistInput, MistOutput};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
 
 pub const TIED_TO_INSTALL_NAME_KEY: &'static str = "mistletoe.dev/tied-to-install-name";
 pub const TIED_TO_INSTALL_VERSION_KEY: &'static str = "mistletoe.dev/tied-to-install-version";
 
+/// Annotation that packages can set to `keep` on resources that should outlive the installation,
+/// e.g. PersistentVolumeClaims or Secrets holding generated credentials.
+pub const RESOURCE_POLICY_KEY: &'static str = "mistletoe.dev/resource-policy";
+pub const RESOURCE_POLICY_KEEP: &'static str = "keep";
+
+/// Annotation that turns a resource into a hook: instead of being installed with the rest, it's
+/// run at the comma-separated [HookPhase]s it lists, e.g. `pre-upgrade` for a Job migrating a
+/// database.
+pub const HOOK_KEY: &'static str = "mistletoe.dev/hook";
+/// Annotation with a comma-separated list of [HookDeletePolicy]s for a hook.  Hooks without it
+/// are deleted before they're created again.
+pub const HOOK_DELETE_POLICY_KEY: &'static str = "mistletoe.dev/hook-delete-policy";
+
+pub const INSTALL_RECORD_OF_KEY: &'static str = "mistletoe.dev/install-record-of";
+pub const INSTALL_RECORD_VERSION_KEY: &'static str = "mistletoe.dev/install-record-version";
+pub const INSTALL_RECORD_TYPE: &'static str = "mistletoe.dev/install-record";
+
+pub const DEFAULT_FIELD_MANAGER: &'static str = "mistctl";
+
+const LIST_CONCURRENCY: usize = 16;
+const WAIT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+const CRD_ESTABLISHED_TIMEOUT: Duration = Duration::from_secs(60);
+const RECENT_EVENTS_WINDOW: Duration = Duration::from_secs(60 * 60);
+pub const DEFAULT_HOOK_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Kinds in the order they're applied in: namespaces and definitions first, then policies, RBAC
+/// and configuration, then the workloads that depend on them.  Kinds not listed here, such as
+/// custom resources, are applied last.
+const APPLY_ORDER: &[&str] = &[
+    "Namespace",
+    "CustomResourceDefinition",
+    "NetworkPolicy",
+    "ResourceQuota",
+    "LimitRange",
+    "PodDisruptionBudget",
+    "ServiceAccount",
+    "Secret",
+    "ConfigMap",
+    "StorageClass",
+    "PersistentVolume",
+    "PersistentVolumeClaim",
+    "ClusterRole",
+    "ClusterRoleBinding",
+    "Role",
+    "RoleBinding",
+    "PriorityClass",
+    "Service",
+    "DaemonSet",
+    "Pod",
+    "ReplicationController",
+    "ReplicaSet",
+    "Deployment",
+    "HorizontalPodAutoscaler",
+    "StatefulSet",
+    "Job",
+    "CronJob",
+    "IngressClass",
+    "Ingress",
+    "APIService",
+    "MutatingWebhookConfiguration",
+    "ValidatingWebhookConfiguration",
+];
+
+/// Built-in kinds that aren't namespaced, which are left alone when setting a default namespace.
+const CLUSTER_SCOPED_KINDS: &[&str] = &[
+    "APIService",
+    "CertificateSigningRequest",
+    "ClusterRole",
+    "ClusterRoleBinding",
+    "CSIDriver",
+    "CSINode",
+    "CustomResourceDefinition",
+    "FlowSchema",
+    "IngressClass",
+    "MutatingWebhookConfiguration",
+    "Namespace",
+    "Node",
+    "PersistentVolume",
+    "PriorityClass",
+    "PriorityLevelConfiguration",
+    "RuntimeClass",
+    "StorageClass",
+    "ValidatingAdmissionPolicy",
+    "ValidatingAdmissionPolicyBinding",
+    "ValidatingWebhookConfiguration",
+    "VolumeAttachment",
+];
+
+/// A connection to the cluster shared by every operation of a command, so that the client is
+/// only created once and discovery only runs once.
+///
+/// If a discovery cache TTL is configured, discovery results are also cached on disk under
+/// `MIST_HOME_LOCATION`, per cluster.  Cached results are refreshed as soon as they're missing a
+/// kind that's asked for, so a stale cache only costs an extra discovery run.
+pub struct ClusterSession {
+    client: Client,
+    cache_file: Option<(PathBuf, Duration)>,
+    discovery: Mutex<Option<Arc<ClusterDiscovery>>>,
+}
+
+impl ClusterSession {
+    pub async fn new() -> anyhow::Result<Self> {
+        let config = cluster::config().await?;
+
+        let cache_file = cluster::options().discovery_cache_ttl.map(|ttl| {
+            let cluster_key: String = config.cluster_url.to_string().chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                .collect();
+            (MIST_HOME_LOCATION.join("cache").join("discovery").join(format!("{}.yaml", cluster_key)), ttl)
+        });
+
+        Ok(Self {
+            client: Client::try_from(config)?,
+            cache_file,
+            discovery: Mutex::new(None),
+        })
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Returns the session's discovery results, running discovery (or loading it from the disk
+    /// cache) the first time it's asked for.
+    pub async fn discovery(&self) -> anyhow::Result<Arc<ClusterDiscovery>> {
+        let mut discovery = self.discovery.lock().await;
+
+        if let Some(discovery) = discovery.as_ref() {
+            return Ok(discovery.clone());
+        }
+
+        let loaded = match self.load_cached_discovery() {
+            Some(cached) => Arc::new(cached),
+            None => Arc::new(self.run_discovery().await?),
+        };
+        *discovery = Some(loaded.clone());
+
+        Ok(loaded)
+    }
+
+    /// Runs discovery again, e.g. after new CustomResourceDefinitions were established.
+    pub async fn refresh_discovery(&self) -> anyhow::Result<Arc<ClusterDiscovery>> {
+        let mut discovery = self.discovery.lock().await;
+
+        let refreshed = Arc::new(self.run_discovery().await?);
+        *discovery = Some(refreshed.clone());
+
+        Ok(refreshed)
+    }
+
+    /// Resolves the kind against the session's discovery results, refreshing them first if
+    /// they came from the disk cache and don't know the kind.
+    pub async fn resolve_gvk(&self, gvk: &GroupVersionKind) -> anyhow::Result<Option<(ApiResource, ApiCapabilities)>> {
+        let discovery = self.discovery().await?;
+
+        match discovery.resolve_gvk(gvk) {
+            None if discovery.cached => Ok(self.refresh_discovery().await?.resolve_gvk(gvk)),
+            resolved => Ok(resolved),
+        }
+    }
+
+    async fn run_discovery(&self) -> anyhow::Result<ClusterDiscovery> {
+        let discovery = ClusterDiscovery::from_discovery(
+            &Discovery::new(self.client.clone()).run().await?);
+
+        // The cache only saves time, so failing to write it isn't worth failing the command over.
+        if let Some((path, _)) = &self.cache_file {
+            let _ = write_discovery_cache(path, &discovery);
+        }
+
+        Ok(discovery)
+    }
+
+    fn load_cached_discovery(&self) -> Option<ClusterDiscovery> {
+        let (path, ttl) = self.cache_file.as_ref()?;
+        let age = std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()?
+            .elapsed().ok()?;
+
+        if age > *ttl {
+            return None;
+        }
+
+        let mut discovery: ClusterDiscovery = serde_yaml::from_str(&std::fs::read_to_string(path).ok()?).ok()?;
+        discovery.cached = true;
+        Some(discovery)
+    }
+}
+
+fn write_discovery_cache(path: &Path, discovery: &ClusterDiscovery) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    Ok(std::fs::write(path, serde_yaml::to_string(discovery)?)?)
+}
+
+/// The resource types served by the cluster, as found by discovery.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct ClusterDiscovery {
+    resources: Vec<DiscoveredResource>,
+    /// Whether these results were loaded from the disk cache rather than discovered just now.
+    #[serde(skip)]
+    cached: bool,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DiscoveredResource {
+    #[serde(flatten)]
+    resource: ApiResource,
+    namespaced: bool,
+    operations: Vec<String>,
+    /// Whether this is the most stable version the resource is served at.
+    preferred: bool,
+}
+
+impl ClusterDiscovery {
+    fn from_discovery(discovery: &Discovery) -> Self {
+        let mut resources = Vec::new();
+
+        for group in discovery.groups() {
+            let preferred: HashSet<(String, String)> = group.resources_by_stability().into_iter()
+                .map(|(ar, _)| (ar.api_version, ar.kind))
+                .collect();
+
+            for version in group.versions() {
+                for (ar, ac) in group.versioned_resources(version) {
+                    resources.push(DiscoveredResource {
+                        preferred: preferred.contains(&(ar.api_version.clone(), ar.kind.clone())),
+                        namespaced: ac.scope == Scope::Namespaced,
+                        operations: ac.operations,
+                        resource: ar,
+                    });
+                }
+            }
+        }
+
+        Self { resources, cached: false }
+    }
+
+    pub fn resolve_gvk(&self, gvk: &GroupVersionKind) -> Option<(ApiResource, ApiCapabilities)> {
+        self.resources.iter()
+            .find(|discovered| discovered.resource.group == gvk.group
+                && discovered.resource.version == gvk.version
+                && discovered.resource.kind == gvk.kind)
+            .map(|discovered| (discovered.resource.clone(), ApiCapabilities {
+                scope: if discovered.namespaced { Scope::Namespaced } else { Scope::Cluster },
+                subresources: Vec::new(),
+                operations: discovered.operations.clone(),
+            }))
+    }
+
+    /// Returns every resource type that can be listed, each under its most stable version only,
+    /// since listing it under every version it's served at would return the same objects more
+    /// than once.
+    pub fn listable_resources(&self) -> Vec<ApiResource> {
+        self.resources.iter()
+            .filter(|discovered| discovered.preferred
+                && discovered.operations.iter().any(|operation| operation == "list"))
+            .map(|discovered| discovered.resource.clone())
+            .collect()
+    }
+}
+
 pub struct InstallRef {
     pub name: String,
     pub version: Option<u32>,
 }
 
 impl InstallRef {
-    pub async fn get_resources(&self) -> anyhow::Result<Vec<DynamicObject>> {
-        let client = Client::try_default().await?;
-        let discovery = Discovery::new(client.clone()).run().await?;
+    /// Returns every revision recorded in the cluster for this installation name, ordered from
+    /// oldest to newest.  If a version is set on this reference, only that revision is returned.
+    pub async fn get_records(&self, session: &ClusterSession) -> anyhow::Result<Vec<InstallRecord>> {
+        let mut records = self.list_record_secrets(session).await?.iter()
+            .map(InstallRecord::from_secret)
+            .collect::<anyhow::Result<Vec<InstallRecord>>>()?;
 
-        let namespaces = Api::<Namespace>::all(client.clone())
-            .list(&ListParams::default()).await?;
+        records.sort_by_key(|record| record.version);
+        Ok(records)
+    }
 
-        let resource_types: Vec<(ApiResource, ApiCapabilities)> = discovery.groups()
-            .flat_map(|group| group.versions()
-                .flat_map(|version| group.versioned_resources(version)))
-            .collect();
+    /// Returns the record for the version set on this reference, or the latest recorded revision
+    /// if no version is set.
+    pub async fn get_record(&self, session: &ClusterSession) -> anyhow::Result<Option<InstallRecord>> {
+        Ok(self.get_records(session).await?.pop())
+    }
+
+    /// Returns the highest revision recorded in the cluster for this installation name, if any.
+    pub async fn get_latest_version(&self, session: &ClusterSession) -> anyhow::Result<Option<u32>> {
+        Ok(self.list_record_secrets(session).await?.iter()
+            .filter_map(|secret| secret.labels().get(INSTALL_RECORD_VERSION_KEY))
+            .filter_map(|version| parse_version_label(version))
+            .max())
+    }
+
+    /// Returns the revision that the next rollout of this installation name should use.
+    pub async fn get_next_version(&self, session: &ClusterSession) -> anyhow::Result<u32> {
+        Ok(self.get_latest_version(session).await?.map(|version| version + 1).unwrap_or(1))
+    }
+
+    /// Stores the record in the cluster as a Secret in the client's default namespace.  This
+    /// fails if the revision has already been recorded.
+    pub async fn write_record(&self, session: &ClusterSession, record: &InstallRecord) -> anyhow::Result<()> {
+        Api::<Secret>::default_namespaced(session.client().clone())
+            .create(&PostParams::default(), &record.to_secret()?).await?;
+
+        Ok(())
+    }
+
+    /// Updates the status of the recorded revision set on this reference.
+    pub async fn set_record_status(&self, session: &ClusterSession, status: InstallStatus) -> anyhow::Result<()> {
+        let version = self.version
+            .ok_or(anyhow!("cannot update the record of installation \"{}\" without a version", self.name))?;
+
+        let secret = self.list_record_secrets(session).await?.into_iter()
+            .find(|secret| secret.labels().get(INSTALL_RECORD_VERSION_KEY)
+                .and_then(|label| parse_version_label(label)) == Some(version))
+            .ok_or(anyhow!("could not find record for installation \"{}\" at revision {}", self.name, version))?;
+
+        let mut data = BTreeMap::new();
+        data.insert("status".to_string(), ByteString(status.as_str().as_bytes().to_vec()));
+        let patch = Secret { data: Some(data), ..Secret::default() };
+
+        Api::<Secret>::namespaced(session.client().clone(), &secret.namespace().unwrap_or_default())
+            .patch(&secret.name_any(), &PatchParams::default(), &Patch::Merge(&patch)).await?;
+
+        Ok(())
+    }
+
+    /// Marks every deployed revision older than the version set on this reference as superseded.
+    pub async fn supersede_records(&self, session: &ClusterSession) -> anyhow::Result<()> {
+        let version = self.version
+            .ok_or(anyhow!("cannot supersede records of installation \"{}\" without a version", self.name))?;
+
+        let records = InstallRef { name: self.name.clone(), version: None }
+            .get_records(session).await?;
+
+        for record in records {
+            if record.version < version && record.status == InstallStatus::Deployed {
+                InstallRef { name: self.name.clone(), version: Some(record.version) }
+                    .set_record_status(session, InstallStatus::Superseded).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Deletes the recorded revisions of this installation name (and version, if set).  Once an
+    /// installation is uninstalled its records go too, so it's no longer listed and can't be
+    /// upgraded or checked for drift.
+    pub async fn delete_records(&self, session: &ClusterSession) -> anyhow::Result<()> {
+        for secret in self.list_record_secrets(session).await? {
+            Api::<Secret>::namespaced(session.client().clone(), &secret.namespace().unwrap_or_default())
+                .delete(&secret.name_any(), &DeleteParams::default()).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn list_record_secrets(&self, session: &ClusterSession) -> anyhow::Result<Vec<Secret>> {
+        let mut label_selector = format!("{}={}", INSTALL_RECORD_OF_KEY, self.name);
+        if let Some(version) = self.version {
+            label_selector += &format!(",{}=v{}", INSTALL_RECORD_VERSION_KEY, version);
+        }
+
+        Ok(Api::<Secret>::all(session.client().clone())
+            .list(&ListParams::default().labels(&label_selector)).await?
+            .items)
+    }
+
+    /// Returns the live resources tied to this installation name (and version, if set).  These
+    /// are looked up from the installation's inventory, falling back to [InstallRef::scan_resources]
+    /// for installations that don't have one.
+    pub async fn get_resources(&self, session: &ClusterSession) -> anyhow::Result<Vec<DynamicObject>> {
+        self.get_resources_in(session, None).await
+    }
+
+    /// Like [InstallRef::get_resources], but namespaced resources the inventory tracked without a
+    /// namespace are looked up in the given one rather than the context's default namespace.
+    pub async fn get_resources_in(&self, session: &ClusterSession, namespace: Option<&str>) -> anyhow::Result<Vec<DynamicObject>> {
+        match Inventory::load(session.client(), &self.name).await? {
+            Some(inventory) => self.get_inventory_resources(session, &inventory, namespace).await,
+            None => self.scan_resources(session).await,
+        }
+    }
 
+    /// Finds the resources tied to this installation name (and version, if set) by scanning every
+    /// resource type the cluster serves for the installation labels.  This is much slower than
+    /// going through the inventory, but also finds resources the inventory lost track of.
+    pub async fn scan_resources(&self, session: &ClusterSession) -> anyhow::Result<Vec<DynamicObject>> {
         let mut label_selector = format!("{}={}", TIED_TO_INSTALL_NAME_KEY, self.name);
         if let Some(version) = self.version {
             label_selector += &format!(",{}=v{}", TIED_TO_INSTALL_VERSION_KEY, version);
         }
 
-        let label_list_params = ListParams::default().labels(&label_selector);
-        let mut resources = Vec::new();
+        list_labeled_resources(session, &label_selector).await
+    }
+
+    /// Rebuilds the installation's inventory from a label scan, returning the resources found.
+    pub async fn repair_inventory(&self, session: &ClusterSession) -> anyhow::Result<Vec<DynamicObject>> {
+        let resources = InstallRef { name: self.name.clone(), version: None }
+            .scan_resources(session).await?;
+
+        let mut inventory = Inventory::load(session.client(), &self.name).await?
+            .unwrap_or_else(|| Inventory::new(&self.name));
+        inventory.entries = resources.iter().filter_map(InventoryEntry::from_resource).collect();
+        inventory.store(session.client()).await?;
+
+        Ok(resources)
+    }
 
-        for (resource_type, resource_caps) in resource_types {
-            if !resource_caps.supports_operation("list") { continue }
+    /// Fetches the live objects tracked by the inventory.  Objects that no longer exist, or that
+    /// are no longer labeled as part of this installation (and version, if set), are left out.
+    async fn get_inventory_resources(&self, session: &ClusterSession, inventory: &Inventory, default_namespace: Option<&str>)
+        -> anyhow::Result<Vec<DynamicObject>>
+    {
+        let client = session.client();
+        let discovery = session.discovery().await?;
 
-            if resource_caps.scope == Scope::Cluster {
-                let api = Api::<DynamicObject>::all_with(
-                    client.clone(),
-                    &resource_type);
+        let resources: Vec<Option<DynamicObject>> = stream::iter(&inventory.entries)
+            .map(|entry| {
+                let discovery = &discovery;
 
-                // TODO: This and the equivalent block in the other case are awful -- we're actively ignoring
-                // list-method results that don't conform, but this could potentially result in us not finding
-                // resources.  We should properly analyze the API or break apart error cases to ensure robustness.
-                if let Ok(object_list) = api.list(&label_list_params).await {
-                    for item in object_list.items {
-                        resources.push(api.get(&item.name_any()).await?);
+                async move {
+                    let (ar, ac) = match discovery.resolve_gvk(&entry.gvk()) {
+                        Some(resolved) => resolved,
+                        None => return Ok(None),
+                    };
+
+                    let api: Api<DynamicObject> = match (&ac.scope, entry.namespace.as_deref().or(default_namespace)) {
+                        (Scope::Namespaced, Some(namespace)) => Api::namespaced_with(client.clone(), namespace, &ar),
+                        (Scope::Namespaced, None) => Api::default_namespaced_with(client.clone(), &ar),
+                        (Scope::Cluster, _) => Api::all_with(client.clone(), &ar),
+                    };
+
+                    let mut resource = api.get_opt(&entry.name).await?;
+                    if let Some(resource) = resource.as_mut() {
+                        resource.types.get_or_insert(TypeMeta {
+                            api_version: ar.api_version.clone(),
+                            kind: ar.kind.clone(),
+                        });
                     }
+
+                    Ok::<_, anyhow::Error>(resource)
                 }
-            } else {
-                for namespace in &namespaces {
-                    let api = Api::<DynamicObject>::namespaced_with(
-                        client.clone(),
-                        namespace.metadata.name.as_ref().unwrap(),
-                        &resource_type);
-
-                    if let Ok(object_list) = api.list(&label_list_params).await {
-                        for item in object_list.items {
-                            resources.push(api.get(&item.name_any()).await?);
-                        }
-                    }
+            })
+            .buffered(LIST_CONCURRENCY)
+            .try_collect()
+            .await?;
+
+        let version_label = self.version.map(|version| format!("v{}", version));
+
+        Ok(resources.into_iter()
+            .flatten()
+            .filter(|resource| resource.labels().get(TIED_TO_INSTALL_NAME_KEY) == Some(&self.name))
+            .filter(|resource| version_label.is_none()
+                || resource.labels().get(TIED_TO_INSTALL_VERSION_KEY) == version_label.as_ref())
+            .collect())
+    }
+
+    /// Server-side applies the given resources.  With a server dry run every resource is still
+    /// sent, and all of the rejections are reported together; with a client dry run nothing is
+    /// sent at all.
+    ///
+    /// Resources whose kind the cluster doesn't serve (and that aren't defined by a
+    /// CustomResourceDefinition among the resources) fail the apply with an [UnknownKindsError]
+    /// before anything is sent, unless `skip_unknown_kinds` is set, in which case they're skipped
+    /// and returned instead.
+    ///
+    /// Live objects that belong to another installation fail the apply with an [OwnershipError]
+    /// before anything is sent, as do live objects that don't belong to any installation unless
+    /// `adopt` is set.
+    pub async fn apply_resources(&self, session: &ClusterSession, install_resources: &InstallResources, options: &ApplyOptions)
+        -> anyhow::Result<Vec<DynamicObject>>
+    {
+        if options.dry_run == DryRun::Client {
+            return Ok(Vec::new());
+        }
+
+        let defined_kinds = install_resources.defined_kinds();
+        let mut unknown = Vec::new();
+        for obj in &install_resources.resources {
+            if let Some(Ok(gvk)) = obj.types.as_ref().map(GroupVersionKind::try_from) {
+                if !defined_kinds.contains(&gvk) && session.resolve_gvk(&gvk).await?.is_none() {
+                    unknown.push(obj.clone());
                 }
             }
         }
 
-        Ok(resources)
+        if !unknown.is_empty() && !options.skip_unknown_kinds {
+            return Err(UnknownKindsError { resources: unknown }.into());
+        }
+
+        self.check_ownership(session, install_resources, options).await?;
+
+        // Everything that gets applied is tracked in the inventory, even if applying fails
+        // partway through, so no applied object goes untracked.
+        let mut applied = Vec::new();
+        let result = self.apply_in_order(session, install_resources, options, &mut applied).await;
+
+        if options.dry_run == DryRun::None && !applied.is_empty() {
+            let mut inventory = Inventory::load(session.client(), &self.name).await?
+                .unwrap_or_else(|| Inventory::new(&self.name));
+            inventory.add(applied.iter().filter_map(InventoryEntry::from_resource).collect());
+            inventory.store(session.client()).await?;
+        }
+
+        result
     }
 
-    pub async fn apply_resources(&self, install_resources: &InstallResources) -> anyhow::Result<()> {
-        let client = Client::try_default().await?;
-        let discovery = Discovery::new(client.clone()).run().await?;
-        let patch_params = PatchParams::apply("mistctl").force();
+    /// Checks that none of the resources already exist as objects of another installation, or
+    /// as unmanaged objects unless they're to be adopted.
+    async fn check_ownership(&self, session: &ClusterSession, install_resources: &InstallResources, options: &ApplyOptions)
+        -> anyhow::Result<()>
+    {
+        let owners: Vec<Option<(&DynamicObject, Option<String>)>> = stream::iter(&install_resources.resources)
+            .map(|obj| async move {
+                let gvk = match obj.types.as_ref().map(GroupVersionKind::try_from) {
+                    Some(Ok(gvk)) => gvk,
+                    _ => return Ok(None),
+                };
+
+                // Kinds the cluster doesn't serve yet can't have live objects.
+                let (ar, ac) = match session.resolve_gvk(&gvk).await? {
+                    Some(resolved) => resolved,
+                    None => return Ok(None),
+                };
+
+                let live = dynamic_api(session.client(), &ar, &ac, obj).get_opt(&obj.name_any()).await?;
+                Ok::<_, anyhow::Error>(live.map(|live| (obj, live.labels().get(TIED_TO_INSTALL_NAME_KEY).cloned())))
+            })
+            .buffered(LIST_CONCURRENCY)
+            .try_collect()
+            .await?;
+
+        let mut owned = Vec::new();
+        let mut unmanaged = Vec::new();
+
+        for (obj, owner) in owners.into_iter().flatten() {
+            match owner {
+                Some(owner) if owner != self.name => owned.push((describe_resource(obj), owner)),
+                Some(_) => {},
+                None if options.adopt || is_created_namespace(obj, options) => {},
+                None => unmanaged.push(describe_resource(obj)),
+            }
+        }
+
+        if owned.is_empty() && unmanaged.is_empty() {
+            Ok(())
+        } else {
+            Err(OwnershipError { owned, unmanaged }.into())
+        }
+    }
+
+    async fn apply_in_order(
+        &self,
+        session: &ClusterSession,
+        install_resources: &InstallResources,
+        options: &ApplyOptions,
+        applied: &mut Vec<DynamicObject>,
+    ) -> anyhow::Result<Vec<DynamicObject>>
+    {
+        let mut patch_params = options.patch_params();
+        patch_params.dry_run = options.dry_run == DryRun::Server;
+
+        let mut rejections = Vec::new();
+        let mut conflicts = Vec::new();
+        let mut skipped = Vec::new();
+        let mut applied_crds = Vec::new();
+
+        // Namespaces aren't created by a server dry run either, so objects in the ones this
+        // rollout creates can't be checked.
+        let pending_namespaces: Vec<&str> = install_resources.resources.iter()
+            .filter(|obj| is_namespace(obj))
+            .filter_map(|obj| obj.metadata.name.as_deref())
+            .chain(options.created_namespace.as_deref())
+            .collect();
+
+        for obj in install_resources.in_apply_order() {
+            // Custom resources can only be resolved once the definitions applied before them are
+            // established and discovery has been run again.
+            if !applied_crds.is_empty() && !is_crd(obj) {
+                self.wait_for_resources(session, &applied_crds, CRD_ESTABLISHED_TIMEOUT).await?;
+                session.refresh_discovery().await?;
+                applied_crds.clear();
+            }
 
-        for obj in &install_resources.resources {
             let gvk = if let Some(tm) = &obj.types {
                 GroupVersionKind::try_from(tm)?
             } else {
                 return Err(anyhow!("could not determine TypeMeta for {:?}", serde_yaml::to_string(&obj)));
             };
 
-            if let Some((ar, ac)) = discovery.resolve_gvk(&gvk) {
-                let api: Api<DynamicObject> = if ac.scope == Scope::Cluster {
-                    Api::all_with(client.clone(), &ar)
-                } else if let Some(namespace) = &obj.metadata.namespace {
-                    Api::namespaced_with(client.clone(), namespace, &ar)
-                } else {
-                    Api::default_namespaced_with(client.clone(), &ar)
-                };
+            let (ar, ac) = match session.resolve_gvk(&gvk).await? {
+                Some(resolved) => resolved,
+                // Definitions aren't created by a server dry run, so their custom resources can't
+                // be checked.
+                None if options.skip_unknown_kinds || options.dry_run == DryRun::Server => {
+                    skipped.push(obj.clone());
+                    continue;
+                },
+                None => return Err(UnknownKindsError { resources: vec![obj.clone()] }.into()),
+            };
 
-                api.patch(
-                    &obj.name_any(),
-                    &patch_params,
-                    &Patch::Apply(serde_yaml::to_value(&obj)?))
-                    .await?;
+            let api = dynamic_api(session.client(), &ar, &ac, obj);
+
+            let result = api.patch(
+                &obj.name_any(),
+                &patch_params,
+                &Patch::Apply(serde_yaml::to_value(&obj)?))
+                .await;
+
+            match result {
+                // Conflicts are collected so that every conflicting field can be reported at once.
+                Err(kube::Error::Api(e)) if e.code == 409 && !options.force_conflicts =>
+                    conflicts.push(ResourceConflicts {
+                        resource: describe_resource(obj),
+                        fields: parse_field_conflicts(&e.message),
+                        message: e.message,
+                    }),
+                Err(kube::Error::Api(e)) if e.code == 404 && options.dry_run == DryRun::Server
+                    && obj.metadata.namespace.as_deref().is_some_and(|ns| pending_namespaces.contains(&ns)) => {},
+                Err(e) if options.dry_run == DryRun::Server =>
+                    rejections.push(format!("{}: {}", describe_resource(obj), e)),
+                Err(e) => return Err(e.into()),
+                Ok(mut applied_obj) => {
+                    applied_obj.types = obj.types.clone();
+                    applied.push(applied_obj);
+                },
+            }
+
+            if is_crd(obj) && options.dry_run == DryRun::None {
+                applied_crds.push(obj.clone());
             }
         }
 
-        Ok(())
+        if !conflicts.is_empty() {
+            return Err(FieldConflictsError { resources: conflicts }.into());
+        }
+
+        if !rejections.is_empty() {
+            return Err(anyhow!("the server rejected {} resource(s) in the dry run:\n{}",
+                rejections.len(), rejections.join("\n")));
+        }
+
+        Ok(skipped)
     }
 
-    pub async fn delete_resources(&self) -> anyhow::Result<Vec<DynamicObject>> {
-        let resources = self.get_resources().await?;
+    pub async fn delete_resources(&self, session: &ClusterSession, options: &DeleteOptions) -> anyhow::Result<DeleteSummary> {
+        let resources = self.get_resources(session).await?;
+        self.delete_objects(session, &resources, options).await
+    }
 
-        let client = Client::try_default().await?;
-        let discovery = Discovery::new(client.clone()).run().await?;
-        let delete_params = DeleteParams::foreground();
+    /// Deletes the given objects from the cluster, in the reverse of the order they'd be applied
+    /// in.  With a client dry run nothing is sent.
+    ///
+    /// Objects annotated with the keep [RESOURCE_POLICY_KEY] policy, or of a kind in
+    /// `keep_kinds`, are left in the cluster but no longer tracked in the inventory.
+    pub async fn delete_objects(&self, session: &ClusterSession, resources: &[DynamicObject], options: &DeleteOptions)
+        -> anyhow::Result<DeleteSummary>
+    {
+        let (kept, deleted): (Vec<DynamicObject>, Vec<DynamicObject>) = resources.iter().cloned()
+            .partition(|resource| options.keeps(resource));
 
-        for resource in &resources {
+        if options.dry_run == DryRun::Client {
+            return Ok(DeleteSummary { deleted, kept });
+        }
+
+        let delete_params = DeleteParams {
+            dry_run: options.dry_run == DryRun::Server,
+            propagation_policy: Some(options.cascade.propagation_policy()),
+            ..DeleteParams::default()
+        };
+
+        let mut ordered: Vec<&DynamicObject> = deleted.iter().collect();
+        ordered.sort_by_key(|resource| std::cmp::Reverse(apply_rank(resource)));
+
+        for resource in ordered {
             let gvk = if let Some(tm) = &resource.types {
                 GroupVersionKind::try_from(tm)?
             } else {
@@ -120,74 +712,1847 @@ impl InstallRef {
                 continue;
             };
 
-            if let Some((ar, ac)) = discovery.resolve_gvk(&gvk) {
-                let api: Api<DynamicObject> = if ac.scope == Scope::Cluster {
-                    Api::all_with(client.clone(), &ar)
-                } else if let Some(namespace) = &resource.metadata.namespace {
-                    Api::namespaced_with(client.clone(), namespace, &ar)
+            let (ar, ac) = session.resolve_gvk(&gvk).await?
+                .ok_or_else(|| UnknownKindsError { resources: vec![resource.clone()] })?;
+            let api = dynamic_api(session.client(), &ar, &ac, resource);
+
+            api.delete(
+                &resource.name_any(),
+                &delete_params)
+                .await?;
+        }
+
+        if options.dry_run == DryRun::None {
+            if let Some(mut inventory) = Inventory::load(session.client(), &self.name).await? {
+                inventory.remove(&resources.iter().filter_map(InventoryEntry::from_resource).collect::<Vec<_>>());
+
+                if inventory.entries.is_empty() {
+                    inventory.delete(session.client()).await?;
                 } else {
-                    Api::default_namespaced_with(client.clone(), &ar)
+                    inventory.store(session.client()).await?;
+                }
+            }
+
+            if let Some(timeout) = options.wait {
+                self.wait_for_deletion(session, &deleted, timeout).await?;
+            }
+        }
+
+        Ok(DeleteSummary { deleted, kept })
+    }
+
+    /// Waits until none of the given resources exist anymore, or fails once the timeout elapses,
+    /// listing the resources that are still around.
+    pub async fn wait_for_deletion(&self, session: &ClusterSession, resources: &[DynamicObject], timeout: Duration) -> anyhow::Result<()> {
+        let deadline = Instant::now() + timeout;
+        let mut remaining: Vec<&DynamicObject> = resources.iter().collect();
+
+        loop {
+            let mut still_remaining = Vec::new();
+
+            for obj in remaining {
+                let gvk = match &obj.types {
+                    Some(tm) => GroupVersionKind::try_from(tm)?,
+                    None => continue,
                 };
 
-                api.delete(
-                    &resource.name_any(),
-                    &delete_params)
-                    .await?;
+                // The kind itself going away (e.g. its definition was deleted) takes its objects
+                // with it.
+                if let Some((ar, ac)) = session.resolve_gvk(&gvk).await? {
+                    if dynamic_api(session.client(), &ar, &ac, obj).get_opt(&obj.name_any()).await?.is_some() {
+                        still_remaining.push(obj);
+                    }
+                }
+            }
+
+            if still_remaining.is_empty() {
+                return Ok(());
+            }
+
+            if Instant::now() >= deadline {
+                return Err(anyhow!("timed out after {}s waiting for {} resource(s) to be deleted:\n{}",
+                    timeout.as_secs(), still_remaining.len(),
+                    still_remaining.iter().map(|obj| describe_resource(obj)).collect::<Vec<_>>().join("\n")));
             }
+
+            remaining = still_remaining;
+            tokio::time::sleep(WAIT_POLL_INTERVAL).await;
         }
+    }
 
-        Ok(resources)
+    /// Returns the live resources tied to this installation name that are not part of the given
+    /// resources, i.e. the ones that a rollout of them would leave behind.
+    pub async fn get_orphaned_resources(&self, session: &ClusterSession, install_resources: &InstallResources)
+        -> anyhow::Result<Vec<DynamicObject>>
+    {
+        let live_resources = InstallRef { name: self.name.clone(), version: None }
+            .get_resources(session).await?;
+
+        let discovery = session.discovery().await?;
+
+        let rendered_keys = install_resources.resources.iter()
+            .map(|obj| {
+                let gvk = obj.types.as_ref().map(GroupVersionKind::try_from).transpose()?;
+                let namespaced = gvk.as_ref()
+                    .and_then(|gvk| discovery.resolve_gvk(gvk))
+                    .map(|(_, ac)| ac.scope == Scope::Namespaced)
+                    .unwrap_or(true);
+
+                Ok(ResourceKey::rendered(obj, namespaced, session.client().default_namespace()))
+            })
+            .collect::<anyhow::Result<HashSet<ResourceKey>>>()?;
+
+        Ok(live_resources.into_iter()
+            .filter(|obj| !rendered_keys.contains(&ResourceKey::new(obj, obj.metadata.namespace.clone())))
+            .collect())
     }
-}
 
-pub struct InstallResources {
-    pub resources: Vec<DynamicObject>,
-}
+    /// Evaluates the health of every live resource of the installation, along with the recent
+    /// warning Events about them.
+    pub async fn get_status(&self, session: &ClusterSession) -> anyhow::Result<InstallHealth> {
+        let resources = self.get_resources(session).await?;
+        let mut warnings = recent_warning_events(session, &resources).await?;
 
-impl InstallResources {
-    pub fn from_str(resources_str: &str) -> anyhow::Result<Self> {
-        let documents = match serde_yaml::from_str::<serde_yaml::Value>(resources_str)? {
-            serde_yaml::Value::Mapping(document) => vec![document],
-            serde_yaml::Value::Sequence(documents) => documents.into_iter()
-                .map(|value| if let serde_yaml::Value::Mapping(document) = value {
-                    Ok(document)
-                } else {
-                    Err(anyhow!("unexpected YAML document value {:?}", value))
-                })
-                .collect::<Result<Vec<Mapping>, _>>()?,
-            value => return Err(anyhow!("unexpected root YAML value {:?}", value)),
+        let mut statuses = Vec::new();
+        for resource in &resources {
+            statuses.push(ResourceStatus {
+                kind: resource.types.as_ref().map(|types| types.kind.clone()).unwrap_or_default(),
+                name: resource.name_any(),
+                namespace: resource.namespace(),
+                health: object_health(session, resource).await?,
+                warnings: resource.uid()
+                    .and_then(|uid| warnings.remove(&uid))
+                    .unwrap_or_default(),
+            });
+        }
+
+        let verdict = if statuses.iter().any(|status| matches!(status.health, Health::Failed(_))) {
+            Verdict::Degraded
+        } else if statuses.iter().any(|status| !status.health.is_ready()) {
+            Verdict::Progressing
+        } else {
+            Verdict::Healthy
         };
 
-        Ok(Self { resources: documents.into_iter()
-            .map(|document| serde_yaml::Value::Mapping(document))
-            .map(|value| serde_yaml::from_value(value))
-            .collect::<Result<Vec<DynamicObject>, _>>()? })
+        Ok(InstallHealth { name: self.name.clone(), verdict, resources: statuses })
     }
 
-    pub fn to_string(&self) -> anyhow::Result<String> {
-        Ok(self.resources.iter()
-            .map(serde_yaml::to_string)
-            .collect::<Result<Vec<String>, _>>()?
-            .join("\n---\n"))
+    /// Returns the hooks of the latest deployed revision.
+    pub async fn get_hooks(&self, session: &ClusterSession) -> anyhow::Result<Vec<Hook>> {
+        let deployed = self.get_records(session).await?.into_iter().rev()
+            .find(|record| record.status == InstallStatus::Deployed);
+
+        match deployed {
+            Some(record) => Ok(InstallResources::from_str(&record.manifest)?.split_hooks()?.1),
+            None => Ok(Vec::new()),
+        }
     }
 
-    pub fn label_resources(mut self, labels: BTreeMap<String, String>) -> anyhow::Result<Self> {
-        for resource in &mut self.resources {
-            let resource_labels = resource.metadata.labels.get_or_insert_with(|| BTreeMap::new());
-            resource_labels.append(&mut labels.clone());
+    /// Runs the hooks of the given phase one after the other, in the order they'd be applied in.
+    /// Each hook is applied and waited on until it has run to completion, as judged by
+    /// [hook_health], then deleted if its [HookDeletePolicy] says so.  The first hook that fails
+    /// or times out fails the phase.
+    ///
+    /// Hooks can't be waited on in a dry run, so nothing is run then.  Returns the hooks that ran.
+    pub async fn run_hooks(&self, session: &ClusterSession, hooks: &[Hook], phase: HookPhase, options: &ApplyOptions, timeout: Duration)
+        -> anyhow::Result<Vec<DynamicObject>>
+    {
+        let mut ordered: Vec<&Hook> = hooks.iter().filter(|hook| hook.runs_in(phase)).collect();
+        ordered.sort_by_key(|hook| apply_rank(&hook.resource));
+
+        if options.dry_run != DryRun::None {
+            return Ok(Vec::new());
         }
 
-        Ok(self)
+        let delete_params = DeleteParams {
+            propagation_policy: Some(PropagationPolicy::Background),
+            ..DeleteParams::default()
+        };
+
+        let mut ran = Vec::new();
+        for hook in ordered {
+            let resource = &hook.resource;
+            let gvk = match &resource.types {
+                Some(tm) => GroupVersionKind::try_from(tm)?,
+                None => continue,
+            };
+
+            let (ar, ac) = session.resolve_gvk(&gvk).await?
+                .ok_or_else(|| UnknownKindsError { resources: vec![resource.clone()] })?;
+            let api = dynamic_api(session.client(), &ar, &ac, resource);
+            let name = resource.name_any();
+
+            // Jobs and Pods can't be changed once they've run, so the previous run has to be gone
+            // before the hook can run again.
+            if hook.deletes_on(HookDeletePolicy::BeforeHookCreation) && api.get_opt(&name).await?.is_some() {
+                api.delete(&name, &delete_params).await?;
+                self.wait_for_deletion(session, std::slice::from_ref(resource), timeout).await?;
+            }
+
+            api.patch(&name, &options.patch_params(), &Patch::Apply(resource)).await?;
+
+            let result = wait_until_ready(session, std::slice::from_ref(resource), timeout, true).await;
+            let delete = match result {
+                Ok(_) => hook.deletes_on(HookDeletePolicy::HookSucceeded),
+                Err(_) => hook.deletes_on(HookDeletePolicy::HookFailed),
+            };
+
+            if delete {
+                api.delete(&name, &delete_params).await?;
+            }
+
+            result.map_err(|e| anyhow!("{} hook failed: {}", phase, e))?;
+            ran.push(resource.clone());
+        }
+
+        Ok(ran)
     }
 
-    pub fn label_resources_with(self, name: &str, version: Option<u32>) -> anyhow::Result<Self> {
-        let mut labels = BTreeMap::new();
-        labels.insert(TIED_TO_INSTALL_NAME_KEY.to_string(), name.to_string());
+    /// Waits until every given resource is healthy, as judged by [resource_health] and
+    /// [service_health].  Fails as soon as a resource fails outright, or once the timeout
+    /// elapses, listing the resources that aren't ready and why.
+    pub async fn wait_for_resources(&self, session: &ClusterSession, resources: &[DynamicObject], timeout: Duration) -> anyhow::Result<()> {
+        wait_until_ready(session, resources, timeout, false).await
+    }
 
-        if let Some(version) = version {
-            labels.insert(TIED_TO_INSTALL_VERSION_KEY.to_string(), format!("v{}", version));
+
+    /// Previews what applying the given resources would change in the cluster.  Each resource is
+    /// server-side applied with a dry run and compared against its live counterpart, and live
+    /// resources that a pruning rollout would delete are included as well.
+    pub async fn diff_resources(&self, session: &ClusterSession, install_resources: &InstallResources, options: &ApplyOptions)
+        -> anyhow::Result<Vec<ResourceDiff>>
+    {
+        let mut diffs = self.diff_against_live(session, install_resources, options).await?;
+
+        for orphan in self.get_orphaned_resources(session, install_resources).await? {
+            let live_yaml = diffable_yaml(&orphan)?;
+            diffs.push(ResourceDiff { resource: orphan, change: ResourceChange::Prune, live: Some(live_yaml), desired: None });
         }
 
-        self.label_resources(labels)
+        Ok(diffs)
+    }
+
+    /// Finds the resources, as last rolled out, whose live counterparts have drifted from them:
+    /// resources that were edited since show up as [ResourceChange::Update] and resources that
+    /// were deleted as [ResourceChange::Create].  Since the comparison goes through a server-side
+    /// apply dry run, fields the server populates or defaults don't count as drift, and neither do
+    /// fields that other field managers own.
+    pub async fn detect_drift(&self, session: &ClusterSession, install_resources: &InstallResources, options: &ApplyOptions)
+        -> anyhow::Result<Vec<ResourceDiff>>
+    {
+        Ok(self.diff_against_live(session, install_resources, options).await?.into_iter()
+            .filter(|diff| diff.change != ResourceChange::Unchanged)
+            .collect())
+    }
+
+    async fn diff_against_live(&self, session: &ClusterSession, install_resources: &InstallResources, options: &ApplyOptions)
+        -> anyhow::Result<Vec<ResourceDiff>>
+    {
+        // Fields taken over from other field managers, e.g. by `kubectl edit`, are exactly what
+        // should show up, so the dry run always forces them back rather than failing on conflicts.
+        let patch_params = options.patch_params().force().dry_run();
+
+        let mut diffs = Vec::new();
+
+        for obj in &install_resources.resources {
+            let gvk = if let Some(tm) = &obj.types {
+                GroupVersionKind::try_from(tm)?
+            } else {
+                return Err(anyhow!("could not determine TypeMeta for {:?}", serde_yaml::to_string(&obj)));
+            };
+
+            // Kinds the cluster doesn't know yet (e.g. custom resources whose definition is part of
+            // the same render) can't be dry-run, so they're compared as rendered.  The same goes for
+            // objects in namespaces that don't exist yet, which the server rejects as not found.
+            let (live, desired) = if let Some((ar, ac)) = session.resolve_gvk(&gvk).await? {
+                let api = dynamic_api(session.client(), &ar, &ac, obj);
+
+                let live = api.get_opt(&obj.name_any()).await?;
+                let desired = api.patch(
+                    &obj.name_any(),
+                    &patch_params,
+                    &Patch::Apply(serde_yaml::to_value(&obj)?))
+                    .await;
+
+                match desired {
+                    Ok(desired) => (live, desired),
+                    Err(kube::Error::Api(e)) if e.code == 404 => (None, obj.clone()),
+                    Err(e) => return Err(e.into()),
+                }
+            } else {
+                (None, obj.clone())
+            };
+
+            let desired_yaml = diffable_yaml(&desired)?;
+            let (change, live_yaml) = match live {
+                Some(live) => {
+                    let live_yaml = diffable_yaml(&live)?;
+                    let change = if live_yaml == desired_yaml { ResourceChange::Unchanged } else { ResourceChange::Update };
+                    (change, Some(live_yaml))
+                },
+                None => (ResourceChange::Create, None),
+            };
+
+            diffs.push(ResourceDiff { resource: desired, change, live: live_yaml, desired: Some(desired_yaml) });
+        }
+
+        Ok(diffs)
+    }
+
+    /// Deletes the live resources tied to this installation name that are not part of the given
+    /// resources, returning the deleted resources.  Resources with the keep policy are left
+    /// behind, untracked.
+    pub async fn prune_resources(&self, session: &ClusterSession, install_resources: &InstallResources, dry_run: DryRun)
+        -> anyhow::Result<Vec<DynamicObject>>
+    {
+        let orphans = self.get_orphaned_resources(session, install_resources).await?;
+        let options = DeleteOptions { dry_run, ..DeleteOptions::default() };
+
+        Ok(self.delete_objects(session, &orphans, &options).await?.deleted)
+    }
+
+    /// Rolls out the given resources as a new revision of this installation: the revision is
+    /// recorded, the resources are labeled with it and applied, and the record's status is
+    /// updated with the outcome.  Dry runs leave the install records untouched.
+    ///
+    /// Hooks among the resources run before and after the rest is applied, in the install or
+    /// upgrade phases depending on [RolloutOptions::upgrade], and a failing hook fails the
+    /// revision.  They're kept in the recorded manifest so that the delete hooks can be run on
+    /// uninstall.
+    pub async fn rollout(
+        &self,
+        session: &ClusterSession,
+        package: &str,
+        input: MistInput,
+        install_resources: InstallResources,
+        options: &RolloutOptions,
+    ) -> anyhow::Result<RolloutSummary>
+    {
+        let version = InstallRef { name: self.name.clone(), version: None }
+            .get_next_version(session).await?;
+        let install_ref = InstallRef { name: self.name.clone(), version: Some(version) };
+        let install_resources = install_resources.label_resources_with(&self.name, Some(version))?;
+        let (resources, hooks) = install_resources.clone().split_hooks()?;
+        let recorded = options.apply.dry_run == DryRun::None;
+
+        if recorded {
+            install_ref.write_record(session, &InstallRecord {
+                name: self.name.clone(),
+                version,
+                package: package.to_string(),
+                input,
+                manifest: install_resources.to_string()?,
+                status: InstallStatus::Pending,
+                field_manager: Some(options.apply.field_manager.clone()),
+                force_conflicts: Some(options.apply.force_conflicts),
+                created_at: None,
+            }).await?;
+        }
+
+        // Objects that don't exist yet are the ones the revision creates, which an atomic rollout
+        // deletes again if it fails.
+        let missing = if options.atomic && recorded {
+            install_ref.missing_resources(session, &resources.resources).await?
+        } else {
+            Vec::new()
+        };
+
+        let (skipped, pruned) = match install_ref.apply_revision(session, &resources, &hooks, options).await {
+            Ok(applied) => applied,
+            Err(e) if recorded => {
+                install_ref.set_record_status(session, InstallStatus::Failed).await?;
+
+                if !options.atomic {
+                    return Err(e);
+                }
+
+                return Err(match install_ref.revert(session, &missing, &options.apply).await {
+                    Ok(reverted) => RolledBackError { version, cause: e, reverted }.into(),
+                    Err(revert_error) => anyhow!("{}\n\nrolling back revision {} failed as well: {}", e, version, revert_error),
+                });
+            },
+            Err(e) => return Err(e),
+        };
+
+        if recorded {
+            install_ref.set_record_status(session, InstallStatus::Deployed).await?;
+            install_ref.supersede_records(session).await?;
+        }
+
+        Ok(RolloutSummary { version, applied: install_resources, pruned, skipped })
+    }
+
+    /// Runs the steps of [InstallRef::rollout] that change the cluster: the pre hooks, applying
+    /// and pruning, waiting, and the post hooks.  Returns the skipped and pruned resources.
+    async fn apply_revision(&self, session: &ClusterSession, resources: &InstallResources, hooks: &[Hook], options: &RolloutOptions)
+        -> anyhow::Result<(Vec<DynamicObject>, Vec<DynamicObject>)>
+    {
+        let hook_timeout = options.wait.unwrap_or(DEFAULT_HOOK_TIMEOUT);
+        let (pre_phase, post_phase) = if options.upgrade {
+            (HookPhase::PreUpgrade, HookPhase::PostUpgrade)
+        } else {
+            (HookPhase::PreInstall, HookPhase::PostInstall)
+        };
+
+        self.run_hooks(session, hooks, pre_phase, &options.apply, hook_timeout).await?;
+
+        let skipped = self.apply_resources(session, resources, &options.apply).await?;
+        let pruned = if options.prune {
+            self.prune_resources(session, resources, options.apply.dry_run).await?
+        } else {
+            Vec::new()
+        };
+
+        // Skipped resources were never applied, so they'd never become ready.
+        if let (DryRun::None, Some(timeout)) = (options.apply.dry_run, options.wait) {
+            let skipped_entries: Vec<InventoryEntry> = skipped.iter()
+                .filter_map(InventoryEntry::from_resource)
+                .collect();
+            let applied: Vec<DynamicObject> = resources.resources.iter()
+                .filter(|resource| InventoryEntry::from_resource(resource)
+                    .is_none_or(|entry| !skipped_entries.contains(&entry)))
+                .cloned()
+                .collect();
+            self.wait_for_resources(session, &applied, timeout).await?;
+        }
+
+        self.run_hooks(session, hooks, post_phase, &options.apply, hook_timeout).await?;
+
+        Ok((skipped, pruned))
+    }
+
+    /// Undoes a failed revision: the given objects that were missing before it was applied and
+    /// exist now are deleted, and the latest deployed revision before it is applied again.
+    async fn revert(&self, session: &ClusterSession, missing: &[DynamicObject], options: &ApplyOptions)
+        -> anyhow::Result<RevertSummary>
+    {
+        let version = self.version.ok_or_else(|| anyhow!("can only revert a specific revision"))?;
+
+        let still_missing: Vec<InventoryEntry> = self.missing_resources(session, missing).await?.iter()
+            .filter_map(InventoryEntry::from_resource)
+            .collect();
+        let created: Vec<DynamicObject> = missing.iter()
+            .filter(|resource| InventoryEntry::from_resource(resource)
+                .is_some_and(|entry| !still_missing.contains(&entry)))
+            .cloned()
+            .collect();
+        let deleted = self.delete_objects(session, &created, &DeleteOptions::default()).await?;
+
+        let previous = InstallRef { name: self.name.clone(), version: None }
+            .get_records(session).await?.into_iter().rev()
+            .find(|record| record.version < version && record.status == InstallStatus::Deployed);
+
+        let restored = match previous {
+            Some(record) => {
+                let (resources, _) = InstallResources::from_str(&record.manifest)?.split_hooks()?;
+                InstallRef { name: self.name.clone(), version: Some(record.version) }
+                    .apply_resources(session, &resources, options).await?;
+                Some((record.version, resources.resources))
+            },
+            None => None,
+        };
+
+        Ok(RevertSummary { deleted: deleted.deleted, kept: deleted.kept, restored })
+    }
+
+    /// Returns the given resources that don't exist in the cluster, including those whose kind
+    /// the cluster doesn't serve.
+    async fn missing_resources(&self, session: &ClusterSession, resources: &[DynamicObject]) -> anyhow::Result<Vec<DynamicObject>> {
+        let missing: Vec<Option<DynamicObject>> = stream::iter(resources)
+            .map(|obj| async move {
+                let gvk = match obj.types.as_ref().map(GroupVersionKind::try_from) {
+                    Some(Ok(gvk)) => gvk,
+                    _ => return Ok(None),
+                };
+
+                let (ar, ac) = match session.resolve_gvk(&gvk).await? {
+                    Some(resolved) => resolved,
+                    None => return Ok(Some(obj.clone())),
+                };
+
+                let live = dynamic_api(session.client(), &ar, &ac, obj).get_opt(&obj.name_any()).await?;
+                Ok::<_, anyhow::Error>(live.is_none().then(|| obj.clone()))
+            })
+            .buffered(LIST_CONCURRENCY)
+            .try_collect()
+            .await?;
+
+        Ok(missing.into_iter().flatten().collect())
+    }
+}
+
+/// Whether, and where, a change to the cluster should only be simulated.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum DryRun {
+    /// Changes are sent to the cluster for real.
+    #[default]
+    None,
+    /// Nothing is sent to the cluster.
+    Client,
+    /// Changes are sent with the API server's `dryRun` parameter, so they go through validation
+    /// and admission without being persisted.
+    Server,
+}
+
+impl DryRun {
+    pub fn from_str(dry_run: &str) -> anyhow::Result<Self> {
+        match dry_run {
+            "none" => Ok(DryRun::None),
+            "client" => Ok(DryRun::Client),
+            "server" => Ok(DryRun::Server),
+            s => Err(anyhow!("unexpected dry run mode \"{}\", can be 'none', 'client' or 'server'", s)),
+        }
+    }
+}
+
+/// Options for [InstallRef::apply_resources].
+#[derive(Clone, Debug)]
+pub struct ApplyOptions {
+    pub dry_run: DryRun,
+    /// Skip resources whose kind the cluster doesn't serve instead of failing.
+    pub skip_unknown_kinds: bool,
+    /// Field manager that owns the applied fields.
+    pub field_manager: String,
+    /// Take over live objects that don't belong to any installation.
+    pub adopt: bool,
+    /// Take over fields owned by other field managers.  Otherwise the conflicting fields are
+    /// reported in a [FieldConflictsError].
+    pub force_conflicts: bool,
+    /// Namespace created for this rollout (or that would be, in a dry run), which the
+    /// installation may take over even though it isn't labeled as one of its objects.
+    pub created_namespace: Option<String>,
+}
+
+impl Default for ApplyOptions {
+    fn default() -> Self {
+        Self {
+            dry_run: DryRun::default(),
+            skip_unknown_kinds: false,
+            adopt: false,
+            field_manager: DEFAULT_FIELD_MANAGER.to_string(),
+            force_conflicts: true,
+            created_namespace: None,
+        }
+    }
+}
+
+impl ApplyOptions {
+    fn patch_params(&self) -> PatchParams {
+        let patch_params = PatchParams::apply(&self.field_manager);
+
+        if self.force_conflicts {
+            patch_params.force()
+        } else {
+            patch_params
+        }
+    }
+}
+
+/// How deleting an object treats the objects it owns, see
+/// [cascading deletion](https://kubernetes.io/docs/concepts/architecture/garbage-collection/#cascading-deletion).
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Cascade {
+    /// The object is deleted right away and its dependents are cleaned up afterwards.
+    Background,
+    /// The object is only deleted once its dependents have been.
+    #[default]
+    Foreground,
+    /// Dependents are left in place.
+    Orphan,
+}
+
+impl Cascade {
+    pub fn from_str(cascade: &str) -> anyhow::Result<Self> {
+        match cascade {
+            "background" => Ok(Cascade::Background),
+            "foreground" => Ok(Cascade::Foreground),
+            "orphan" => Ok(Cascade::Orphan),
+            s => Err(anyhow!("unexpected cascade mode \"{}\", can be 'background', 'foreground' or 'orphan'", s)),
+        }
+    }
+
+    fn propagation_policy(&self) -> PropagationPolicy {
+        match self {
+            Cascade::Background => PropagationPolicy::Background,
+            Cascade::Foreground => PropagationPolicy::Foreground,
+            Cascade::Orphan => PropagationPolicy::Orphan,
+        }
+    }
+}
+
+/// Options for [InstallRef::delete_objects].
+#[derive(Clone, Debug, Default)]
+pub struct DeleteOptions {
+    pub dry_run: DryRun,
+    pub cascade: Cascade,
+    /// Kinds to leave in the cluster, on top of the resources annotated with the keep policy.
+    /// These are matched case-insensitively.
+    pub keep_kinds: Vec<String>,
+    /// Wait up to the given timeout for the deleted resources to be gone.
+    pub wait: Option<Duration>,
+}
+
+impl DeleteOptions {
+    /// Whether the resource should be left in the cluster rather than deleted.
+    pub fn keeps(&self, resource: &DynamicObject) -> bool {
+        let kind = resource.types.as_ref().map(|types| types.kind.as_str()).unwrap_or_default();
+
+        resource.annotations().get(RESOURCE_POLICY_KEY).map(String::as_str) == Some(RESOURCE_POLICY_KEEP)
+            || self.keep_kinds.iter().any(|keep_kind| keep_kind.eq_ignore_ascii_case(kind))
+    }
+}
+
+/// What deleting a set of resources did.
+#[derive(Clone, Debug, Default)]
+pub struct DeleteSummary {
+    pub deleted: Vec<DynamicObject>,
+    /// The resources left in the cluster because of a keep policy.
+    pub kept: Vec<DynamicObject>,
+}
+
+/// When a hook runs, see [HOOK_KEY].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HookPhase {
+    PreInstall,
+    PostInstall,
+    PreUpgrade,
+    PostUpgrade,
+    PreDelete,
+    PostDelete,
+}
+
+impl HookPhase {
+    pub fn from_str(phase: &str) -> anyhow::Result<Self> {
+        match phase {
+            "pre-install" => Ok(HookPhase::PreInstall),
+            "post-install" => Ok(HookPhase::PostInstall),
+            "pre-upgrade" => Ok(HookPhase::PreUpgrade),
+            "post-upgrade" => Ok(HookPhase::PostUpgrade),
+            "pre-delete" => Ok(HookPhase::PreDelete),
+            "post-delete" => Ok(HookPhase::PostDelete),
+            s => Err(anyhow!("unexpected hook phase \"{}\", can be 'pre-install', 'post-install', \
+                'pre-upgrade', 'post-upgrade', 'pre-delete' or 'post-delete'", s)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HookPhase::PreInstall => "pre-install",
+            HookPhase::PostInstall => "post-install",
+            HookPhase::PreUpgrade => "pre-upgrade",
+            HookPhase::PostUpgrade => "post-upgrade",
+            HookPhase::PreDelete => "pre-delete",
+            HookPhase::PostDelete => "post-delete",
+        }
+    }
+}
+
+impl fmt::Display for HookPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// When a hook is deleted again, see [HOOK_DELETE_POLICY_KEY].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HookDeletePolicy {
+    /// The previous run of the hook is deleted right before it runs again.
+    BeforeHookCreation,
+    /// The hook is deleted as soon as it has succeeded.
+    HookSucceeded,
+    /// The hook is deleted as soon as it has failed.
+    HookFailed,
+}
+
+impl HookDeletePolicy {
+    pub fn from_str(policy: &str) -> anyhow::Result<Self> {
+        match policy {
+            "before-hook-creation" => Ok(HookDeletePolicy::BeforeHookCreation),
+            "hook-succeeded" => Ok(HookDeletePolicy::HookSucceeded),
+            "hook-failed" => Ok(HookDeletePolicy::HookFailed),
+            s => Err(anyhow!("unexpected hook delete policy \"{}\", can be 'before-hook-creation', \
+                'hook-succeeded' or 'hook-failed'", s)),
+        }
+    }
+}
+
+/// A resource that's run at certain points of an installation's lifecycle rather than installed
+/// with the rest of it.  Hooks aren't tracked in the inventory, so they're never pruned.
+#[derive(Clone, Debug)]
+pub struct Hook {
+    pub resource: DynamicObject,
+    pub phases: Vec<HookPhase>,
+    pub delete_policies: Vec<HookDeletePolicy>,
+}
+
+impl Hook {
+    /// Reads the hook annotations of the resource, or returns `None` if it isn't a hook.
+    pub fn from_resource(resource: &DynamicObject) -> anyhow::Result<Option<Self>> {
+        let annotations = resource.annotations();
+        let phases = match annotations.get(HOOK_KEY) {
+            Some(phases) => split_list(phases).map(HookPhase::from_str).collect::<anyhow::Result<Vec<_>>>()?,
+            None => return Ok(None),
+        };
+        let delete_policies = match annotations.get(HOOK_DELETE_POLICY_KEY) {
+            Some(policies) => split_list(policies).map(HookDeletePolicy::from_str).collect::<anyhow::Result<Vec<_>>>()?,
+            None => vec![HookDeletePolicy::BeforeHookCreation],
+        };
+
+        Ok(Some(Self { resource: resource.clone(), phases, delete_policies }))
+    }
+
+    pub fn runs_in(&self, phase: HookPhase) -> bool {
+        self.phases.contains(&phase)
+    }
+
+    pub fn deletes_on(&self, policy: HookDeletePolicy) -> bool {
+        self.delete_policies.contains(&policy)
+    }
+}
+
+/// What undoing a failed revision did, see [RolloutOptions::atomic].
+#[derive(Clone, Debug, Default)]
+pub struct RevertSummary {
+    /// The objects the failed revision created, which were deleted again.
+    pub deleted: Vec<DynamicObject>,
+    /// The objects the failed revision created that were left in place because of a keep policy.
+    pub kept: Vec<DynamicObject>,
+    /// The revision that was applied again, with its resources, if there was a deployed one.
+    pub restored: Option<(u32, Vec<DynamicObject>)>,
+}
+
+/// Error for a revision that failed and was undone.
+#[derive(Debug)]
+pub struct RolledBackError {
+    /// The revision that failed.
+    pub version: u32,
+    /// Why it failed.
+    pub cause: anyhow::Error,
+    pub reverted: RevertSummary,
+}
+
+impl fmt::Display for RolledBackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "revision {} failed and was rolled back: {}", self.version, self.cause)?;
+
+        for resource in &self.reverted.deleted {
+            write!(f, "\n  deleted {}", describe_resource(resource))?;
+        }
+        for resource in &self.reverted.kept {
+            write!(f, "\n  kept {}", describe_resource(resource))?;
+        }
+        match &self.reverted.restored {
+            Some((version, resources)) => {
+                write!(f, "\n  restored revision {}:", version)?;
+                for resource in resources {
+                    write!(f, "\n    {}", describe_resource(resource))?;
+                }
+            },
+            None => write!(f, "\n  there was no previous revision to restore")?,
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for RolledBackError {}
+
+/// Options for [InstallRef::rollout].
+#[derive(Clone, Debug, Default)]
+pub struct RolloutOptions {
+    /// Delete live resources that aren't part of the new revision.
+    pub prune: bool,
+    /// Whether this rolls out a new revision of an existing installation, which runs the upgrade
+    /// hooks instead of the install hooks.
+    pub upgrade: bool,
+    pub apply: ApplyOptions,
+    /// Wait up to the given timeout for the applied resources to become healthy.
+    pub wait: Option<Duration>,
+    /// Undo the revision if any step fails: the objects it created are deleted and the previous
+    /// deployed revision is applied again.  The failure is reported as a [RolledBackError].
+    pub atomic: bool,
+}
+
+
+/// Overall health of an installation.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize)]
+pub enum Verdict {
+    /// Every resource is ready.
+    Healthy,
+    /// Some resources aren't ready yet, but none have failed.
+    Progressing,
+    /// Some resources have failed.
+    Degraded,
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// Health of an installation's live resources, see [InstallRef::get_status].
+#[derive(Clone, Debug, Serialize)]
+pub struct InstallHealth {
+    pub name: String,
+    pub verdict: Verdict,
+    pub resources: Vec<ResourceStatus>,
+}
+
+/// Health of a single live resource of an installation.
+#[derive(Clone, Debug, Serialize)]
+pub struct ResourceStatus {
+    pub kind: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    pub health: Health,
+    /// Recent warning Events about the resource, newest first.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// Summary of an installation found in the cluster.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstallSummary {
+    pub name: String,
+    /// Package of the latest recorded revision, if the installation has been recorded.
+    pub package: Option<String>,
+    /// Latest recorded revision, if the installation has been recorded.
+    pub version: Option<u32>,
+    /// Status of the latest recorded revision, if the installation has been recorded.
+    pub status: Option<String>,
+    /// Namespaces that the installation's resources live in.
+    pub namespaces: Vec<String>,
+    /// Number of resources tied to the installation.
+    pub resource_count: usize,
+    /// When the latest revision was recorded.
+    pub last_applied: Option<String>,
+}
+
+/// Summarizes every installation in the cluster.  Installations are discovered from their
+/// install records and inventories.  With `scan`, resources labeled with an installation name
+/// are scanned for as well, which also finds installs that predate records and inventories.
+pub async fn list_installs(session: &ClusterSession, scan: bool) -> anyhow::Result<Vec<InstallSummary>> {
+    let mut latest_records: BTreeMap<String, InstallRecord> = BTreeMap::new();
+    for secret in Api::<Secret>::all(session.client().clone()).list(&ListParams::default().labels(INSTALL_RECORD_OF_KEY)).await? {
+        let record = InstallRecord::from_secret(&secret)?;
+        if latest_records.get(&record.name).is_none_or(|latest| latest.version < record.version) {
+            latest_records.insert(record.name.clone(), record);
+        }
+    }
+
+    let mut install_resources: BTreeMap<String, Vec<InventoryEntry>> = BTreeMap::new();
+    for inventory in Inventory::load_all(session.client()).await? {
+        install_resources.insert(inventory.name, inventory.entries);
+    }
+
+    if scan {
+        for resource in list_labeled_resources(session, TIED_TO_INSTALL_NAME_KEY).await? {
+            let name = resource.labels().get(TIED_TO_INSTALL_NAME_KEY).cloned();
+            if let (Some(name), Some(entry)) = (name, InventoryEntry::from_resource(&resource)) {
+                let entries = install_resources.entry(name).or_default();
+                if !entries.iter().any(|existing| existing.is_same_object(&entry)) {
+                    entries.push(entry);
+                }
+            }
+        }
+    }
+
+    let names: BTreeSet<String> = latest_records.keys()
+        .chain(install_resources.keys())
+        .cloned()
+        .collect();
+
+    Ok(names.into_iter()
+        .map(|name| {
+            let record = latest_records.get(&name);
+            let resources = install_resources.get(&name).map(Vec::as_slice).unwrap_or_default();
+
+            InstallSummary {
+                package: record.map(|record| record.package.clone()),
+                version: record.map(|record| record.version),
+                status: record.map(|record| record.status.as_str().to_string()),
+                namespaces: resources.iter()
+                    .filter_map(|entry| entry.namespace.clone())
+                    .collect::<BTreeSet<String>>()
+                    .into_iter().collect(),
+                resource_count: resources.len(),
+                last_applied: record
+                    .and_then(|record| record.created_at.as_ref())
+                    .map(|time| time.0.to_rfc3339()),
+                name,
+            }
+        })
+        .collect())
+}
+
+/// Lists the resources matching the label selector across every resource type the cluster
+/// serves.  Each resource type is listed once across all namespaces, with a bounded number of
+/// requests in flight at a time.  Resource types the client isn't allowed to list or that
+/// disappear in the meantime are passed over, but any other failure is returned.
+async fn list_labeled_resources(session: &ClusterSession, label_selector: &str) -> anyhow::Result<Vec<DynamicObject>> {
+    let resource_types = session.discovery().await?.listable_resources();
+
+    let label_list_params = ListParams::default().labels(label_selector);
+
+    let resource_lists: Vec<Vec<DynamicObject>> = stream::iter(resource_types)
+        .map(|resource_type| {
+            let api = Api::<DynamicObject>::all_with(session.client().clone(), &resource_type);
+            let label_list_params = &label_list_params;
+
+            async move {
+                match api.list(label_list_params).await {
+                    // List items don't carry their own type information, so it's filled in from
+                    // the resource type they were listed under.
+                    Ok(object_list) => Ok(object_list.items.into_iter()
+                        .map(|mut item| {
+                            item.types = Some(TypeMeta {
+                                api_version: resource_type.api_version.clone(),
+                                kind: resource_type.kind.clone(),
+                            });
+                            item
+                        })
+                        .collect()),
+                    Err(kube::Error::Api(e)) if [403, 404, 405].contains(&e.code) => Ok(Vec::new()),
+                    Err(e) => Err(anyhow!("could not list {}: {}", resource_type.plural, e)),
+                }
+            }
+        })
+        .buffered(LIST_CONCURRENCY)
+        .try_collect()
+        .await?;
+
+    Ok(resource_lists.into_iter().flatten().collect())
+}
+
+/// How applying a resource would change the cluster.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ResourceChange {
+    Create,
+    Update,
+    Unchanged,
+    /// The resource is live but no longer rendered, so a pruning rollout would delete it.
+    Prune,
+}
+
+/// The change a rollout would make to a single resource, see [InstallRef::diff_resources].
+pub struct ResourceDiff {
+    /// The resource as it would be after the rollout, or the live resource if it would be pruned.
+    pub resource: DynamicObject,
+    pub change: ResourceChange,
+    /// YAML of the live resource, if it exists.
+    pub live: Option<String>,
+    /// YAML of the resource after the rollout, if it wouldn't be pruned.
+    pub desired: Option<String>,
+}
+
+/// Serializes the resource for comparison, leaving out the fields that the server maintains
+/// on its own and the revision label, which every rollout changes.
+fn diffable_yaml(resource: &DynamicObject) -> anyhow::Result<String> {
+    let mut resource = resource.clone();
+    resource.metadata.managed_fields = None;
+    resource.metadata.resource_version = None;
+    resource.metadata.generation = None;
+    resource.metadata.uid = None;
+    resource.metadata.creation_timestamp = None;
+
+    if let Some(data) = resource.data.as_object_mut() {
+        data.remove("status");
+    }
+
+    if let Some(labels) = resource.metadata.labels.as_mut() {
+        labels.remove(TIED_TO_INSTALL_VERSION_KEY);
+    }
+
+    Ok(serde_yaml::to_string(&resource)?)
+}
+
+/// Summary of a rollout performed by [InstallRef::rollout].
+pub struct RolloutSummary {
+    /// The revision that was rolled out.
+    pub version: u32,
+    /// The labeled resources that were applied.
+    pub applied: InstallResources,
+    /// Resources that were deleted because the new revision no longer contains them.
+    pub pruned: Vec<DynamicObject>,
+    /// Resources that were skipped because the cluster doesn't serve their kind.
+    pub skipped: Vec<DynamicObject>,
+}
+
+/// Error for resources whose kind the cluster doesn't serve.
+#[derive(Debug)]
+pub struct UnknownKindsError {
+    pub resources: Vec<DynamicObject>,
+}
+
+impl fmt::Display for UnknownKindsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the cluster doesn't serve the kinds of {} resource(s):", self.resources.len())?;
+        for resource in &self.resources {
+            write!(f, "\n  {}", describe_unknown_kind(resource))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for UnknownKindsError {}
+
+/// Error for resources that already exist in the cluster without belonging to the installation
+/// being applied.
+#[derive(Debug)]
+pub struct OwnershipError {
+    /// Descriptions of the resources that belong to another installation, with its name.
+    pub owned: Vec<(String, String)>,
+    /// Descriptions of the resources that don't belong to any installation.
+    pub unmanaged: Vec<String>,
+}
+
+impl fmt::Display for OwnershipError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} resource(s) already exist without belonging to this installation:",
+            self.owned.len() + self.unmanaged.len())?;
+
+        for (resource, owner) in &self.owned {
+            write!(f, "\n  {} belongs to installation \"{}\"", resource, owner)?;
+        }
+        for resource in &self.unmanaged {
+            write!(f, "\n  {} isn't managed by mistctl, use --adopt to take it over", resource)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for OwnershipError {}
+
+/// Error for resources that couldn't be applied because other field managers own some of their
+/// fields.
+#[derive(Debug)]
+pub struct FieldConflictsError {
+    pub resources: Vec<ResourceConflicts>,
+}
+
+/// The fields of a resource that other field managers own.
+#[derive(Clone, Debug)]
+pub struct ResourceConflicts {
+    /// Description of the resource, e.g. `deployment.apps/my-app (in namespace my-namespace)`.
+    pub resource: String,
+    pub fields: Vec<FieldConflict>,
+    /// The server's error message, for when the fields couldn't be made out from it.
+    pub message: String,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct FieldConflict {
+    /// The field manager that owns the field.
+    pub manager: String,
+    /// Path of the field, e.g. `.spec.replicas`.
+    pub field: String,
+}
+
+impl fmt::Display for FieldConflictsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "other field managers own fields of {} resource(s), remove the fields from the package or \
+            force the apply to take them over:", self.resources.len())?;
+
+        for resource in &self.resources {
+            write!(f, "\n  {}:", resource.resource)?;
+
+            if resource.fields.is_empty() {
+                write!(f, "\n    {}", resource.message)?;
+            }
+            for conflict in &resource.fields {
+                write!(f, "\n    {} (managed by \"{}\")", conflict.field, conflict.manager)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for FieldConflictsError {}
+
+/// Makes out the conflicting fields from the API server's apply conflict message, which looks
+/// like `Apply failed with 1 conflict: conflict with "manager" using apps/v1: .spec.replicas` for
+/// a single conflict, or lists the fields under a `conflicts with "manager"` line per manager for
+/// multiple conflicts.
+fn parse_field_conflicts(message: &str) -> Vec<FieldConflict> {
+    let mut conflicts = Vec::new();
+    let mut manager: Option<String> = None;
+
+    for line in message.lines().map(str::trim) {
+        if let Some(field) = line.strip_prefix("- ") {
+            if let Some(manager) = &manager {
+                conflicts.push(FieldConflict { manager: manager.clone(), field: field.to_string() });
+            }
+        } else if let Some((_, rest)) = line.split_once("conflicts with ") {
+            manager = leading_quoted(rest);
+        } else if let Some((_, rest)) = line.split_once("conflict with ") {
+            if let (Some(manager), Some((_, field))) = (leading_quoted(rest), rest.rsplit_once(": ")) {
+                conflicts.push(FieldConflict { manager, field: field.to_string() });
+            }
+        }
+    }
+
+    conflicts
+}
+
+fn leading_quoted(s: &str) -> Option<String> {
+    s.strip_prefix('"')?.split_once('"').map(|(quoted, _)| quoted.to_string())
+}
+
+/// Describes the resource by its full apiVersion and kind, e.g.
+/// `example.com/v1 Database my-db (in namespace my-namespace)`.
+pub fn describe_unknown_kind(resource: &DynamicObject) -> String {
+    let (api_version, kind) = resource.types.as_ref()
+        .map(|types| (types.api_version.as_str(), types.kind.as_str()))
+        .unwrap_or(("<unknown apiVersion>", "<unknown kind>"));
+
+    match &resource.metadata.namespace {
+        Some(namespace) => format!("{} {} {} (in namespace {})", api_version, kind, resource.name_any(), namespace),
+        None => format!("{} {} {}", api_version, kind, resource.name_any()),
+    }
+}
+
+/// Picks the API for the object's resource type, scoped to the object's namespace if the type
+/// is namespaced, falling back to the client's default namespace.
+fn dynamic_api(client: &Client, ar: &ApiResource, ac: &ApiCapabilities, obj: &DynamicObject) -> Api<DynamicObject> {
+    if ac.scope == Scope::Cluster {
+        Api::all_with(client.clone(), ar)
+    } else if let Some(namespace) = &obj.metadata.namespace {
+        Api::namespaced_with(client.clone(), namespace, ar)
+    } else {
+        Api::default_namespaced_with(client.clone(), ar)
+    }
+}
+
+/// Returns the warning Events of the last [RECENT_EVENTS_WINDOW] about the given resources, keyed
+/// by the UID of the resource they're about, newest first.  Events are listed once per namespace
+/// the resources live in, with Events about cluster-scoped resources living in `default`.
+async fn recent_warning_events(session: &ClusterSession, resources: &[DynamicObject])
+    -> anyhow::Result<HashMap<String, Vec<String>>>
+{
+    let namespaces: BTreeSet<String> = resources.iter()
+        .map(|resource| resource.namespace().unwrap_or("default".to_string()))
+        .collect();
+    let uids: HashSet<String> = resources.iter().filter_map(|resource| resource.uid()).collect();
+    let since = Utc::now() - chrono::Duration::from_std(RECENT_EVENTS_WINDOW)?;
+
+    let mut events = Vec::new();
+    for namespace in namespaces {
+        events.extend(Api::<Event>::namespaced(session.client().clone(), &namespace)
+            .list(&ListParams::default().fields("type=Warning")).await?
+            .items);
+    }
+
+    let last_seen = |event: &Event| event.last_timestamp.as_ref().map(|time| time.0)
+        .or(event.event_time.as_ref().map(|time| time.0))
+        .or(event.metadata.creation_timestamp.as_ref().map(|time| time.0));
+
+    events.retain(|event| event.involved_object.uid.as_ref().is_some_and(|uid| uids.contains(uid))
+        && last_seen(event).is_some_and(|time| time >= since));
+    events.sort_by_key(|event| std::cmp::Reverse(last_seen(event)));
+
+    let mut warnings: HashMap<String, Vec<String>> = HashMap::new();
+    for event in events {
+        let message = format!("{}: {}{}",
+            event.reason.as_deref().unwrap_or("Warning"),
+            event.message.as_deref().unwrap_or_default().trim(),
+            match event.count { Some(count) if count > 1 => format!(" (x{})", count), _ => String::new() });
+
+        warnings.entry(event.involved_object.uid.clone().unwrap_or_default())
+            .or_default()
+            .push(message);
+    }
+
+    Ok(warnings)
+}
+
+/// Waits until every given resource is healthy, or has run to completion as judged by
+/// [hook_health].  See [InstallRef::wait_for_resources].
+async fn wait_until_ready(session: &ClusterSession, resources: &[DynamicObject], timeout: Duration, to_completion: bool)
+    -> anyhow::Result<()>
+{
+    let deadline = Instant::now() + timeout;
+
+    let mut pending: Vec<&DynamicObject> = resources.iter().collect();
+
+    loop {
+        let mut still_pending = Vec::new();
+        let mut reasons = Vec::new();
+
+        for obj in pending {
+            let health = live_health(session, obj, to_completion).await?;
+
+            match health {
+                Health::Ready => {},
+                Health::Failed(reason) =>
+                    return Err(anyhow!("{} failed: {}", describe_resource(obj), reason)),
+                Health::Progressing(reason) => {
+                    reasons.push(format!("{}: {}", describe_resource(obj), reason));
+                    still_pending.push(obj);
+                },
+            }
+        }
+
+        if still_pending.is_empty() {
+            return Ok(());
+        }
+
+        if Instant::now() >= deadline {
+            return Err(anyhow!("timed out after {}s waiting for {} resource(s) to become ready:\n{}",
+                timeout.as_secs(), reasons.len(), reasons.join("\n")));
+        }
+
+        pending = still_pending;
+        tokio::time::sleep(WAIT_POLL_INTERVAL).await;
+    }
+}
+
+/// Fetches the live counterpart of the object and evaluates its health.  Objects that don't
+/// exist (yet) or whose kind the cluster doesn't know are considered progressing.
+async fn live_health(session: &ClusterSession, obj: &DynamicObject, to_completion: bool) -> anyhow::Result<Health> {
+    let gvk = match &obj.types {
+        Some(tm) => GroupVersionKind::try_from(tm)?,
+        None => return Ok(Health::Ready),
+    };
+
+    let (ar, ac) = match session.resolve_gvk(&gvk).await? {
+        Some(resolved) => resolved,
+        None => return Ok(Health::Progressing("the cluster doesn't know this kind yet".to_string())),
+    };
+
+    let api = dynamic_api(session.client(), &ar, &ac, obj);
+    let live = match api.get_opt(&obj.name_any()).await? {
+        Some(live) => live,
+        None => return Ok(Health::Progressing("the resource doesn't exist".to_string())),
+    };
+
+    if to_completion {
+        Ok(hook_health(&live))
+    } else {
+        object_health(session, &live).await
+    }
+}
+
+/// Evaluates the health of an object fetched from the cluster.
+async fn object_health(session: &ClusterSession, live: &DynamicObject) -> anyhow::Result<Health> {
+    let is_service = live.types.as_ref()
+        .is_some_and(|types| types.api_version == "v1" && types.kind == "Service");
+
+    if is_service {
+        let endpoints = Api::<Endpoints>::namespaced(session.client().clone(), &live.namespace().unwrap_or_default())
+            .get_opt(&live.name_any()).await?;
+        return Ok(service_health(live, endpoints.as_ref()));
+    }
+
+    Ok(resource_health(live))
+}
+
+fn apply_rank(resource: &DynamicObject) -> usize {
+    let kind = resource.types.as_ref().map(|types| types.kind.as_str()).unwrap_or_default();
+    APPLY_ORDER.iter().position(|ordered| *ordered == kind).unwrap_or(APPLY_ORDER.len())
+}
+
+/// Whether the API group is served by Kubernetes itself rather than a custom resource definition.
+fn is_builtin_group(group: &str) -> bool {
+    !group.contains('.') || group.ends_with(".k8s.io")
+}
+
+fn is_crd(resource: &DynamicObject) -> bool {
+    resource.types.as_ref().is_some_and(|types| types.kind == "CustomResourceDefinition")
+}
+
+fn is_namespace(resource: &DynamicObject) -> bool {
+    resource.types.as_ref().is_some_and(|types| types.api_version == "v1" && types.kind == "Namespace")
+}
+
+fn is_created_namespace(resource: &DynamicObject, options: &ApplyOptions) -> bool {
+    is_namespace(resource) && options.created_namespace.is_some()
+        && options.created_namespace == resource.metadata.name
+}
+
+/// Splits a comma-separated annotation value into its trimmed, non-empty items.
+fn split_list(list: &str) -> impl Iterator<Item = &str> {
+    list.split(',').map(str::trim).filter(|item| !item.is_empty())
+}
+
+/// Describes the resource for messages, e.g. `deployment.apps/my-app (in namespace my-namespace)`.
+fn describe_resource(resource: &DynamicObject) -> String {
+    let full_type = match &resource.types {
+        Some(types) => match types.api_version.rsplit_once("/") {
+            Some((group, _)) => format!("{}.{}", types.kind.to_lowercase(), group),
+            None => types.kind.to_lowercase(),
+        },
+        None => "<unknown kind>".to_string(),
+    };
+
+    match &resource.metadata.namespace {
+        Some(namespace) => format!("{}/{} (in namespace {})", full_type, resource.name_any(), namespace),
+        None => format!("{}/{}", full_type, resource.name_any()),
+    }
+}
+
+/// Identifies a resource independently of its API version, so the same object rendered under
+/// a different version of its group still matches.
+#[derive(PartialEq, Eq, Hash)]
+struct ResourceKey {
+    group: String,
+    kind: String,
+    namespace: Option<String>,
+    name: String,
+}
+
+impl ResourceKey {
+    fn new(obj: &DynamicObject, namespace: Option<String>) -> Self {
+        let (group, kind) = match &obj.types {
+            Some(types) => (
+                types.api_version.rsplit_once("/").map(|(group, _)| group.to_string()).unwrap_or_default(),
+                types.kind.clone(),
+            ),
+            None => (String::new(), String::new()),
+        };
+
+        Self { group, kind, namespace, name: obj.name_any() }
+    }
+
+    /// Keys a rendered object the way the API server will store it: namespaced objects without a
+    /// namespace land in the default one, and cluster-scoped objects never have one, even if the
+    /// render sets it.
+    fn rendered(obj: &DynamicObject, namespaced: bool, default_namespace: &str) -> Self {
+        let namespace = match (&obj.metadata.namespace, namespaced) {
+            (_, false) => None,
+            (Some(namespace), true) => Some(namespace.clone()),
+            (None, true) => Some(default_namespace.to_string()),
+        };
+
+        Self::new(obj, namespace)
+    }
+}
+
+/// The status of a recorded installation revision.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum InstallStatus {
+    /// The revision has been recorded but its resources haven't finished applying.
+    Pending,
+    /// The revision's resources were applied successfully.
+    Deployed,
+    /// Applying the revision's resources failed.
+    Failed,
+    /// The revision was deployed, but a newer revision has since replaced it.
+    Superseded,
+}
+
+impl InstallStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InstallStatus::Pending => "pending",
+            InstallStatus::Deployed => "deployed",
+            InstallStatus::Failed => "failed",
+            InstallStatus::Superseded => "superseded",
+        }
+    }
+
+    pub fn from_str(status: &str) -> anyhow::Result<Self> {
+        match status {
+            "pending" => Ok(InstallStatus::Pending),
+            "deployed" => Ok(InstallStatus::Deployed),
+            "failed" => Ok(InstallStatus::Failed),
+            "superseded" => Ok(InstallStatus::Superseded),
+            s => Err(anyhow!("unexpected installation status \"{}\"", s)),
+        }
+    }
+}
+
+/// A single revision of an installation, as recorded in the cluster.
+///
+/// This holds everything that went into the rollout, so it can be audited or reproduced
+/// without the package that originally rendered it.
+#[derive(Clone, PartialEq, Debug)]
+pub struct InstallRecord {
+    /// Name of the installation.
+    pub name: String,
+    /// Revision of the installation.
+    pub version: u32,
+    /// The package reference the revision was rendered from.
+    pub package: String,
+    /// The merged input that was passed to the package.
+    pub input: MistInput,
+    /// The rendered and labeled manifest that was applied to the cluster.
+    pub manifest: String,
+    /// Status of the revision.
+    pub status: InstallStatus,
+    /// Field manager the revision was applied as.  Later rollouts keep using it unless told
+    /// otherwise.
+    pub field_manager: Option<String>,
+    /// Whether the revision took over fields owned by other field managers.  Later rollouts keep
+    /// doing so unless told otherwise.
+    pub force_conflicts: Option<bool>,
+    /// When the revision was recorded, if it has been stored in the cluster.
+    pub created_at: Option<Time>,
+}
+
+impl InstallRecord {
+    pub fn to_secret(&self) -> anyhow::Result<Secret> {
+        let mut labels = BTreeMap::new();
+        labels.insert(INSTALL_RECORD_OF_KEY.to_string(), self.name.clone());
+        labels.insert(INSTALL_RECORD_VERSION_KEY.to_string(), format!("v{}", self.version));
+
+        let mut data = BTreeMap::new();
+        data.insert("package".to_string(), ByteString(self.package.as_bytes().to_vec()));
+        data.insert("input".to_string(), ByteString(serde_yaml::to_string(&self.input)?.into_bytes()));
+        data.insert("manifest".to_string(), ByteString(self.manifest.as_bytes().to_vec()));
+        data.insert("status".to_string(), ByteString(self.status.as_str().as_bytes().to_vec()));
+        if let Some(field_manager) = &self.field_manager {
+            data.insert("fieldManager".to_string(), ByteString(field_manager.as_bytes().to_vec()));
+        }
+        if let Some(force_conflicts) = self.force_conflicts {
+            data.insert("forceConflicts".to_string(), ByteString(force_conflicts.to_string().into_bytes()));
+        }
+
+        Ok(Secret {
+            metadata: ObjectMeta {
+                name: Some(format!("mistletoe.install.{}.v{}", self.name, self.version)),
+                labels: Some(labels),
+                ..ObjectMeta::default()
+            },
+            type_: Some(INSTALL_RECORD_TYPE.to_string()),
+            data: Some(data),
+            ..Secret::default()
+        })
+    }
+
+    pub fn from_secret(secret: &Secret) -> anyhow::Result<Self> {
+        let labels = secret.labels();
+        let name = labels.get(INSTALL_RECORD_OF_KEY)
+            .ok_or(anyhow!("install record \"{}\" is missing the {} label", secret.name_any(), INSTALL_RECORD_OF_KEY))?;
+        let version = labels.get(INSTALL_RECORD_VERSION_KEY)
+            .and_then(|version| parse_version_label(version))
+            .ok_or(anyhow!("install record \"{}\" has a missing or malformed {} label", secret.name_any(), INSTALL_RECORD_VERSION_KEY))?;
+
+        let empty = BTreeMap::new();
+        let data = secret.data.as_ref().unwrap_or(&empty);
+        let field = |key: &str| -> anyhow::Result<String> {
+            let value = data.get(key)
+                .ok_or(anyhow!("install record \"{}\" is missing the \"{}\" field", secret.name_any(), key))?;
+            Ok(String::from_utf8(value.0.clone())?)
+        };
+        // Records from before these settings were kept don't have them.
+        let optional_field = |key: &str| -> anyhow::Result<Option<String>> {
+            data.get(key).map(|value| String::from_utf8(value.0.clone())).transpose().map_err(Into::into)
+        };
+
+        Ok(Self {
+            name: name.clone(),
+            version,
+            package: field("package")?,
+            input: serde_yaml::from_str(&field("input")?)?,
+            manifest: field("manifest")?,
+            status: InstallStatus::from_str(&field("status")?)?,
+            field_manager: optional_field("fieldManager")?,
+            force_conflicts: optional_field("forceConflicts")?
+                .map(|force_conflicts| force_conflicts.parse())
+                .transpose()?,
+            created_at: secret.metadata.creation_timestamp.clone(),
+        })
+    }
+}
+
+fn parse_version_label(version: &str) -> Option<u32> {
+    version.strip_prefix("v").and_then(|version| version.parse().ok())
+}
+
+#[derive(Clone)]
+pub struct InstallResources {
+    pub resources: Vec<DynamicObject>,
+}
+
+impl InstallResources {
+    pub fn from_output(output: &MistOutput) -> anyhow::Result<Self> {
+        let mut resources = Vec::new();
+        for (_, content) in output.get_files() {
+            resources.append(&mut Self::from_str(content)?.resources);
+        }
+
+        Ok(Self { resources })
+    }
+
+    pub fn from_str(resources_str: &str) -> anyhow::Result<Self> {
+        let mut documents = Vec::new();
+
+        // Manifests may hold multiple `---`-separated documents, each of which is either a single
+        // resource or a sequence of them.
+        for document in serde_yaml::Deserializer::from_str(resources_str) {
+            match serde_yaml::Value::deserialize(document)? {
+                serde_yaml::Value::Mapping(document) => documents.push(document),
+                serde_yaml::Value::Sequence(values) => for value in values {
+                    if let serde_yaml::Value::Mapping(document) = value {
+                        documents.push(document);
+                    } else {
+                        return Err(anyhow!("unexpected YAML document value {:?}", value));
+                    }
+                },
+                serde_yaml::Value::Null => continue,
+                value => return Err(anyhow!("unexpected root YAML value {:?}", value)),
+            }
+        }
+
+        Ok(Self { resources: documents.into_iter()
+            .map(|document| serde_yaml::Value::Mapping(document))
+            .map(|value| serde_yaml::from_value(value))
+            .collect::<Result<Vec<DynamicObject>, _>>()? })
+    }
+
+    /// Returns the kinds defined by the CustomResourceDefinitions among the resources, for every
+    /// version they serve.
+    pub fn defined_kinds(&self) -> HashSet<GroupVersionKind> {
+        self.resources.iter()
+            .filter(|resource| is_crd(resource))
+            .flat_map(|crd| {
+                let spec = &crd.data["spec"];
+                let group = spec["group"].as_str().unwrap_or_default().to_string();
+                let kind = spec["names"]["kind"].as_str().unwrap_or_default().to_string();
+
+                spec["versions"].as_array().cloned().unwrap_or_default().into_iter()
+                    .filter_map(|version| version["name"].as_str().map(str::to_string))
+                    .map(move |version| GroupVersionKind::gvk(&group, &version, &kind))
+            })
+            .collect()
+    }
+
+    /// Sets the namespace on every namespaced resource that doesn't specify one.  Cluster-scoped
+    /// resources are recognized by kind, including the custom resources of cluster-scoped
+    /// definitions among the resources.
+    pub fn with_default_namespace(mut self, namespace: &str) -> Self {
+        let cluster_scoped_kinds: HashSet<(String, String)> = self.resources.iter()
+            .filter(|resource| is_crd(resource) && resource.data["spec"]["scope"].as_str() == Some("Cluster"))
+            .map(|crd| {
+                let spec = &crd.data["spec"];
+                (spec["group"].as_str().unwrap_or_default().to_string(),
+                    spec["names"]["kind"].as_str().unwrap_or_default().to_string())
+            })
+            .collect();
+
+        for resource in &mut self.resources {
+            let (group, kind) = match &resource.types {
+                Some(types) => (types.api_version.split_once("/").map(|(group, _)| group).unwrap_or_default(), types.kind.as_str()),
+                None => continue,
+            };
+
+            let cluster_scoped = (is_builtin_group(group) && CLUSTER_SCOPED_KINDS.contains(&kind))
+                || cluster_scoped_kinds.contains(&(group.to_string(), kind.to_string()));
+
+            if !cluster_scoped && resource.metadata.namespace.is_none() {
+                resource.metadata.namespace = Some(namespace.to_string());
+            }
+        }
+
+        self
+    }
+
+    /// Returns the resources in the order they should be applied in, see [APPLY_ORDER].  Resources
+    /// of the same kind keep the order they were rendered in.
+    pub fn in_apply_order(&self) -> Vec<&DynamicObject> {
+        let mut ordered: Vec<&DynamicObject> = self.resources.iter().collect();
+        ordered.sort_by_key(|resource| apply_rank(resource));
+        ordered
+    }
+
+    /// Separates the hooks, see [HOOK_KEY], from the resources that are installed.
+    pub fn split_hooks(self) -> anyhow::Result<(Self, Vec<Hook>)> {
+        let mut resources = Vec::new();
+        let mut hooks = Vec::new();
+
+        for resource in self.resources {
+            match Hook::from_resource(&resource)? {
+                Some(hook) => hooks.push(hook),
+                None => resources.push(resource),
+            }
+        }
+
+        Ok((Self { resources }, hooks))
+    }
+
+    pub fn to_string(&self) -> anyhow::Result<String> {
+        Ok(self.resources.iter()
+            .map(serde_yaml::to_string)
+            .collect::<Result<Vec<String>, _>>()?
+            .join("\n---\n"))
+    }
+
+    pub fn label_resources(mut self, labels: BTreeMap<String, String>) -> anyhow::Result<Self> {
+        for resource in &mut self.resources {
+            let resource_labels = resource.metadata.labels.get_or_insert_with(|| BTreeMap::new());
+            resource_labels.append(&mut labels.clone());
+        }
+
+        Ok(self)
+    }
+
+    pub fn label_resources_with(self, name: &str, version: Option<u32>) -> anyhow::Result<Self> {
+        let mut labels = BTreeMap::new();
+        labels.insert(TIED_TO_INSTALL_NAME_KEY.to_string(), name.to_string());
+
+        if let Some(version) = version {
+            labels.insert(TIED_TO_INSTALL_VERSION_KEY.to_string(), format!("v{}", version));
+        }
+
+        self.label_resources(labels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_install_record_secret() {
+        let mut data = serde_yaml::Mapping::new();
+        data.insert("name".into(), "my-namespace".into());
+
+        let record = InstallRecord {
+            name: "my-namespace".to_string(),
+            version: 3,
+            package: "mistletoe/examples/namespace-example:0.1.2".to_string(),
+            input: MistInput { data },
+            manifest: indoc! {"
+                apiVersion: v1
+                kind: Namespace
+                metadata:
+                  name: my-namespace
+                  labels:
+                    mistletoe.dev/tied-to-install-name: my-namespace
+                    mistletoe.dev/tied-to-install-version: v3"}.to_string(),
+            status: InstallStatus::Deployed,
+            field_manager: Some("my-controller".to_string()),
+            force_conflicts: Some(false),
+            created_at: None,
+        };
+
+        let secret = record.to_secret().unwrap();
+        assert_eq!(Some("mistletoe.install.my-namespace.v3".to_string()), secret.metadata.name);
+        assert_eq!(Some(&"v3".to_string()), secret.labels().get(INSTALL_RECORD_VERSION_KEY));
+
+        let record_parsed = InstallRecord::from_secret(&secret).unwrap();
+        assert_eq!(record, record_parsed);
+    }
+
+    #[test]
+    fn test_install_resources_multiple_documents() {
+        let manifest = indoc! {"
+            apiVersion: v1
+            kind: Namespace
+            metadata:
+              name: my-namespace
+            ---
+            apiVersion: v1
+            kind: ConfigMap
+            metadata:
+              name: my-config
+              namespace: my-namespace
+            ---
+        "};
+
+        let install_resources = InstallResources::from_str(manifest).unwrap();
+        assert_eq!(2, install_resources.resources.len());
+        assert_eq!("my-config", install_resources.resources[1].name_any());
+
+        let reparsed = InstallResources::from_str(&install_resources.to_string().unwrap()).unwrap();
+        assert_eq!(2, reparsed.resources.len());
+    }
+
+    #[test]
+    fn test_install_resources_apply_order() {
+        let manifest = indoc! {"
+            apiVersion: example.com/v1
+            kind: Database
+            metadata:
+              name: my-db
+            ---
+            apiVersion: apps/v1
+            kind: Deployment
+            metadata:
+              name: my-app
+            ---
+            apiVersion: apiextensions.k8s.io/v1
+            kind: CustomResourceDefinition
+            metadata:
+              name: databases.example.com
+            ---
+            apiVersion: v1
+            kind: ConfigMap
+            metadata:
+              name: my-config
+            ---
+            apiVersion: v1
+            kind: Namespace
+            metadata:
+              name: my-namespace
+        "};
+
+        let install_resources = InstallResources::from_str(manifest).unwrap();
+        let kinds: Vec<String> = install_resources.in_apply_order().iter()
+            .map(|resource| resource.types.as_ref().unwrap().kind.clone())
+            .collect();
+
+        assert_eq!(vec!["Namespace", "CustomResourceDefinition", "ConfigMap", "Deployment", "Database"], kinds);
+    }
+
+    #[test]
+    fn test_install_resources_default_namespace() {
+        let manifest = indoc! {"
+            apiVersion: apps/v1
+            kind: Deployment
+            metadata:
+              name: my-app
+            ---
+            apiVersion: v1
+            kind: ConfigMap
+            metadata:
+              name: my-config
+              namespace: elsewhere
+            ---
+            apiVersion: rbac.authorization.k8s.io/v1
+            kind: ClusterRole
+            metadata:
+              name: my-role
+            ---
+            apiVersion: apiextensions.k8s.io/v1
+            kind: CustomResourceDefinition
+            metadata:
+              name: clusters.example.com
+            spec:
+              group: example.com
+              scope: Cluster
+              names:
+                kind: Cluster
+            ---
+            apiVersion: example.com/v1
+            kind: Cluster
+            metadata:
+              name: my-cluster
+        "};
+
+        let install_resources = InstallResources::from_str(manifest).unwrap()
+            .with_default_namespace("my-namespace");
+        let namespaces: Vec<Option<&str>> = install_resources.resources.iter()
+            .map(|resource| resource.metadata.namespace.as_deref())
+            .collect();
+
+        assert_eq!(vec![Some("my-namespace"), Some("elsewhere"), None, None, None], namespaces);
+    }
+
+    #[test]
+    fn test_rendered_resource_key() {
+        let manifest = indoc! {"
+            apiVersion: rbac.authorization.k8s.io/v1
+            kind: ClusterRole
+            metadata:
+              name: my-role
+              namespace: my-namespace
+            ---
+            apiVersion: v1
+            kind: ConfigMap
+            metadata:
+              name: my-config
+        "};
+
+        let install_resources = InstallResources::from_str(manifest).unwrap();
+        let cluster_role = &install_resources.resources[0];
+        let config_map = &install_resources.resources[1];
+
+        assert!(ResourceKey::rendered(cluster_role, false, "default") == ResourceKey::new(cluster_role, None));
+        assert!(ResourceKey::rendered(config_map, true, "default") == ResourceKey::new(config_map, Some("default".to_string())));
+    }
+
+    #[test]
+    fn test_delete_options_keeps() {
+        let manifest = indoc! {"
+            apiVersion: v1
+            kind: PersistentVolumeClaim
+            metadata:
+              name: my-data
+            ---
+            apiVersion: v1
+            kind: Secret
+            metadata:
+              name: my-credentials
+              annotations:
+                mistletoe.dev/resource-policy: keep
+            ---
+            apiVersion: apps/v1
+            kind: Deployment
+            metadata:
+              name: my-app
+        "};
+
+        let install_resources = InstallResources::from_str(manifest).unwrap();
+        let options = DeleteOptions { keep_kinds: vec!["persistentvolumeclaim".to_string()], ..DeleteOptions::default() };
+        let kept: Vec<bool> = install_resources.resources.iter()
+            .map(|resource| options.keeps(resource))
+            .collect();
+
+        assert_eq!(vec![true, true, false], kept);
+    }
+
+    #[test]
+    fn test_split_hooks() {
+        let manifest = indoc! {"
+            apiVersion: batch/v1
+            kind: Job
+            metadata:
+              name: migrate
+              annotations:
+                mistletoe.dev/hook: pre-install, pre-upgrade
+                mistletoe.dev/hook-delete-policy: hook-succeeded
+            ---
+            apiVersion: apps/v1
+            kind: Deployment
+            metadata:
+              name: my-app
+        "};
+
+        let (install_resources, hooks) = InstallResources::from_str(manifest).unwrap().split_hooks().unwrap();
+        assert_eq!(vec!["my-app".to_string()], install_resources.resources.iter().map(|resource| resource.name_any()).collect::<Vec<_>>());
+        assert_eq!(1, hooks.len());
+        assert_eq!(vec![HookPhase::PreInstall, HookPhase::PreUpgrade], hooks[0].phases);
+        assert_eq!(vec![HookDeletePolicy::HookSucceeded], hooks[0].delete_policies);
+
+        let invalid = indoc! {"
+            apiVersion: batch/v1
+            kind: Job
+            metadata:
+              name: migrate
+              annotations:
+                mistletoe.dev/hook: pre-rollback
+        "};
+        assert!(InstallResources::from_str(invalid).unwrap().split_hooks().is_err());
+    }
+
+    #[test]
+    fn test_parse_field_conflicts() {
+        let single = "Apply failed with 1 conflict: conflict with \"kube-controller-manager\" using apps/v1: .spec.replicas";
+        assert_eq!(vec![FieldConflict { manager: "kube-controller-manager".to_string(), field: ".spec.replicas".to_string() }],
+            parse_field_conflicts(single));
+
+        let multiple = indoc! {"
+            Apply failed with 3 conflicts: conflicts with \"hpa-controller\" using apps/v1:
+            - .spec.replicas
+            conflicts with \"kubectl-edit\" using apps/v1:
+            - .metadata.labels.team
+            - .spec.template.spec.containers[name=\"app\"].image
+        "};
+        let fields: Vec<(String, String)> = parse_field_conflicts(multiple).into_iter()
+            .map(|conflict| (conflict.manager, conflict.field))
+            .collect();
+        assert_eq!(vec![
+            ("hpa-controller".to_string(), ".spec.replicas".to_string()),
+            ("kubectl-edit".to_string(), ".metadata.labels.team".to_string()),
+            ("kubectl-edit".to_string(), ".spec.template.spec.containers[name=\"app\"].image".to_string()),
+        ], fields);
     }
 }
@@ -1,9 +1,11 @@
 use crate::config::ConfigLayout;
 use crate::registry::Remote;
 
+use std::fmt;
 use std::path::{Path, PathBuf};
 
 use anyhow::anyhow;
+use semver::{Version, VersionReq};
 use mistletoe_api::v1alpha1::{MistPackage, MistResult, deserialize_result};
 use wasmer::{
     Store,
@@ -15,16 +17,20 @@ use wasmer::{
 };
 
 
+#[derive(Clone, PartialEq, Debug)]
 pub enum MistPackageRef {
     Local(PathBuf),
     Remote {
         registry: String,
         package: String,
-        version: String,
+        version: PackageVersion,
     },
 }
 
 impl MistPackageRef {
+    /// Parses a package reference.  Paths are local packages, and anything else is a remote
+    /// package in the form `<registry>/<package>[:<version>]`, see [PackageVersion] for the
+    /// versions it can ask for.
     pub fn from_str(package: &str) -> anyhow::Result<Self> {
         let package_path = PathBuf::from(package);
 
@@ -40,30 +46,98 @@ impl MistPackageRef {
         }
 
         let package_parts: Vec<&str> = package.split(":").collect();
-        if package_parts.len() < 2 {
-            return Err(anyhow!("version must always be specified, in the form `<package>:<version>`"));
-        }
         if package_parts.len() > 2 {
-            return Err(anyhow!("expected only one ':', found {}", package_parts.len()-1));
+            return Err(anyhow!("expected at most one ':', found {}", package_parts.len()-1));
         }
 
         let remote_path = Path::new(package_parts.get(0).unwrap());
-        let remote_version = package_parts.get(1).unwrap();
+        let remote_version = match package_parts.get(1) {
+            Some(version) => PackageVersion::from_str(version)?,
+            None => PackageVersion::Latest,
+        };
 
         let remote_registry = remote_path.iter().next().unwrap();
         let remote_package = remote_path.iter()
             .skip(1).map(|p| PathBuf::from(p)).reduce(|p1, p2| p1.join(p2))
-            .unwrap();
+            .ok_or_else(|| anyhow!("expected a package in the form `<registry>/<package>[:<version>]`, got \"{}\"", package))?;
 
         Ok(Self::Remote {
             registry: remote_registry.to_str().unwrap().to_string(),
             package: remote_package.to_str().unwrap().to_string(),
-            version: remote_version.to_string(),
+            version: remote_version,
         })
     }
 }
 
+impl fmt::Display for MistPackageRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MistPackageRef::Local(package_path) => write!(f, "{}", package_path.display()),
+            MistPackageRef::Remote { registry, package, version } =>
+                write!(f, "{}/{}:{}", registry, package, version),
+        }
+    }
+}
+
+/// The version asked for by a remote package reference.
+#[derive(Clone, PartialEq, Debug)]
+pub enum PackageVersion {
+    /// Exactly this version, e.g. `0.1.2`.
+    Exact(String),
+    /// The newest version in a semver range, e.g. `^0.1` or `~0.1.2`.
+    Range(VersionReq),
+    /// The newest stable version, given as `latest` or by leaving the version out.
+    Latest,
+}
+
+impl PackageVersion {
+    pub fn from_str(version: &str) -> anyhow::Result<Self> {
+        if version == "latest" {
+            return Ok(PackageVersion::Latest);
+        }
+
+        // Plain versions would parse as ranges too, `0.1.2` meaning `^0.1.2`.
+        if Version::parse(version).is_ok() {
+            return Ok(PackageVersion::Exact(version.to_string()));
+        }
+
+        VersionReq::parse(version)
+            .map(PackageVersion::Range)
+            .map_err(|e| anyhow!("invalid version or version range \"{}\": {}", version, e))
+    }
+
+    /// Picks the newest of the available versions that satisfies this one.  Pre-releases are only
+    /// picked by a range that asks for them, or for `latest` if there's nothing else.
+    pub fn resolve(&self, available: &[Version]) -> Option<Version> {
+        match self {
+            PackageVersion::Exact(version) => available.iter()
+                .find(|available| available.to_string() == *version)
+                .cloned(),
+            PackageVersion::Range(range) => available.iter()
+                .filter(|available| range.matches(available))
+                .max()
+                .cloned(),
+            PackageVersion::Latest => available.iter()
+                .filter(|available| available.pre.is_empty())
+                .max()
+                .or_else(|| available.iter().max())
+                .cloned(),
+        }
+    }
+}
+
+impl fmt::Display for PackageVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PackageVersion::Exact(version) => write!(f, "{}", version),
+            PackageVersion::Range(range) => write!(f, "{}", range),
+            PackageVersion::Latest => write!(f, "latest"),
+        }
+    }
+}
+
 pub struct MistPackageInstance {
+    package_ref: MistPackageRef,
     local: bool,
     store: Store,
     instance: Instance,
@@ -76,7 +150,7 @@ impl MistPackageInstance {
         match package_ref {
             MistPackageRef::Local(package_path) => {
                 let module = Module::from_file(&store, package_path)?;
-                Ok(MistPackageInstance::init(true, store, module)?)
+                Ok(MistPackageInstance::init(package_ref.clone(), true, store, module)?)
             },
             MistPackageRef::Remote { registry, package, version } => {
                 let remote = Remote::default_for_name(
@@ -86,21 +160,38 @@ impl MistPackageInstance {
                 remote.init()?;
                 remote.pull()?;
 
+                let version = match version {
+                    PackageVersion::Exact(version) => version.clone(),
+                    version => {
+                        let available = remote.list_versions(Path::new(package))?;
+                        version.resolve(&available)
+                            .ok_or_else(|| anyhow!("no version of package {} matches \"{}\", available versions are: {}",
+                                package, version, available.iter().map(Version::to_string).collect::<Vec<_>>().join(", ")))?
+                            .to_string()
+                    },
+                };
+
                 let package_path = remote
-                    .lookup_package(&PathBuf::from(package), version)
+                    .lookup_package(&PathBuf::from(package), &version)
                     .ok_or_else(|| anyhow!("could not find package at {}", package))?;
 
                 let module = Module::from_file(&store, package_path)?;
-                Ok(MistPackageInstance::init(true, store, module)?)
+                let resolved_ref = MistPackageRef::Remote {
+                    registry: registry.clone(),
+                    package: package.clone(),
+                    version: PackageVersion::Exact(version),
+                };
+                Ok(MistPackageInstance::init(resolved_ref, true, store, module)?)
             }
         }
     }
 
-    fn init(local: bool, mut store: Store, module: Module) -> anyhow::Result<Self> {
+    fn init(package_ref: MistPackageRef, local: bool, mut store: Store, module: Module) -> anyhow::Result<Self> {
         let import_object = imports! {};
         let instance = Instance::new(&mut store, &module, &import_object)?;
 
         Ok(Self {
+            package_ref,
             local,
             store,
             instance,
@@ -111,6 +202,12 @@ impl MistPackageInstance {
         self.local
     }
 
+    /// The reference the package was loaded from, with the version it resolved to for remote
+    /// packages.
+    pub fn package_ref(&self) -> &MistPackageRef {
+        &self.package_ref
+    }
+
     fn info_from_instance(store: &mut Store, instance: &Instance, memory: &Memory)
         -> anyhow::Result<MistPackage>
     {
@@ -186,3 +283,38 @@ impl MistPackageInstance {
         Ok(output)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_package_ref_versions() {
+        let versions = |package: &str| match MistPackageRef::from_str(package).unwrap() {
+            MistPackageRef::Remote { version, .. } => version,
+            MistPackageRef::Local(_) => panic!("expected a remote package"),
+        };
+
+        assert_eq!(PackageVersion::Exact("0.1.2".to_string()), versions("mistletoe/examples/foo:0.1.2"));
+        assert_eq!(PackageVersion::Range(VersionReq::parse("^0.1").unwrap()), versions("mistletoe/examples/foo:^0.1"));
+        assert_eq!(PackageVersion::Latest, versions("mistletoe/examples/foo:latest"));
+        assert_eq!(PackageVersion::Latest, versions("mistletoe/examples/foo"));
+        assert!(MistPackageRef::from_str("mistletoe/examples/foo:^x").is_err());
+    }
+
+    #[test]
+    fn test_package_version_resolve() {
+        let available: Vec<Version> = ["0.1.1", "0.1.2", "0.2.0", "0.3.0-beta.1"].iter()
+            .map(|version| Version::parse(version).unwrap())
+            .collect();
+        let resolve = |version: &str| PackageVersion::from_str(version).unwrap()
+            .resolve(&available)
+            .map(|version| version.to_string());
+
+        assert_eq!(Some("0.1.2".to_string()), resolve("^0.1"));
+        assert_eq!(Some("0.1.2".to_string()), resolve("~0.1.1"));
+        assert_eq!(Some("0.2.0".to_string()), resolve("latest"));
+        assert_eq!(Some("0.1.1".to_string()), resolve("0.1.1"));
+        assert_eq!(None, resolve("^1.0"));
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::BTreeMap;
+
+use anyhow::anyhow;
+use k8s_openapi::api::core::v1::ConfigMap;
+use kube::{Api, Client, ResourceExt};
+use kube::api::{DeleteParams, ListParams, ObjectMeta, Patch, PatchParams};
+use kube::core::{DynamicObject, GroupVersionKind};
+use serde::{Deserialize, Serialize};
+
+pub const INVENTORY_OF_KEY: &'static str = "mistletoe.dev/inventory-of";
+
+const INVENTORY_DATA_KEY: &'static str = "inventory";
+
+/// A single object tracked by an [Inventory].
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InventoryEntry {
+    pub api_version: String,
+    pub kind: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    pub name: String,
+}
+
+impl InventoryEntry {
+    /// Creates an entry for the resource, or returns `None` if it has no type information.
+    pub fn from_resource(resource: &DynamicObject) -> Option<Self> {
+        let types = resource.types.as_ref()?;
+
+        Some(Self {
+            api_version: types.api_version.clone(),
+            kind: types.kind.clone(),
+            namespace: resource.metadata.namespace.clone(),
+            name: resource.name_any(),
+        })
+    }
+
+    pub fn gvk(&self) -> GroupVersionKind {
+        match self.api_version.split_once("/") {
+            Some((group, version)) => GroupVersionKind::gvk(group, version, &self.kind),
+            None => GroupVersionKind::gvk("", &self.api_version, &self.kind),
+        }
+    }
+
+    /// Whether both entries track the same object.  The API version is ignored, since the same
+    /// object can be applied under different versions of its group.
+    pub fn is_same_object(&self, other: &InventoryEntry) -> bool {
+        self.gvk().group == other.gvk().group
+            && self.kind == other.kind
+            && self.namespace == other.namespace
+            && self.name == other.name
+    }
+}
+
+/// The list of every object applied for an installation.
+///
+/// This is stored in the cluster as a ConfigMap, so an installation's resources can be found
+/// without scanning every resource type the cluster serves for the installation labels.
+#[derive(Clone, Debug)]
+pub struct Inventory {
+    /// Name of the installation.
+    pub name: String,
+    /// Namespace the inventory is stored in, if it has been stored yet.
+    pub namespace: Option<String>,
+    pub entries: Vec<InventoryEntry>,
+}
+
+impl Inventory {
+    pub fn new(name: &str) -> Self {
+        Self { name: name.to_string(), namespace: None, entries: Vec::new() }
+    }
+
+    /// Loads the inventory of the installation from the cluster, if it has one.
+    pub async fn load(client: &Client, name: &str) -> anyhow::Result<Option<Self>> {
+        let label_selector = format!("{}={}", INVENTORY_OF_KEY, name);
+
+        Api::<ConfigMap>::all(client.clone())
+            .list(&ListParams::default().labels(&label_selector)).await?
+            .items.first()
+            .map(Self::from_config_map)
+            .transpose()
+    }
+
+    /// Loads every inventory in the cluster.
+    pub async fn load_all(client: &Client) -> anyhow::Result<Vec<Self>> {
+        Api::<ConfigMap>::all(client.clone())
+            .list(&ListParams::default().labels(INVENTORY_OF_KEY)).await?
+            .items.iter()
+            .map(Self::from_config_map)
+            .collect()
+    }
+
+    /// Stores the inventory in the cluster, in the namespace it was loaded from or the client's
+    /// default namespace if it's new.
+    pub async fn store(&mut self, client: &Client) -> anyhow::Result<()> {
+        let namespace = self.namespace.clone()
+            .unwrap_or(client.default_namespace().to_string());
+
+        let config_map = self.to_config_map()?;
+        Api::<ConfigMap>::namespaced(client.clone(), &namespace)
+            .patch(
+                &config_map.name_any(),
+                &PatchParams::apply("mistctl").force(),
+                &Patch::Apply(&config_map))
+            .await?;
+
+        self.namespace = Some(namespace);
+        Ok(())
+    }
+
+    /// Deletes the inventory from the cluster, if it has been stored.
+    pub async fn delete(&self, client: &Client) -> anyhow::Result<()> {
+        if let Some(namespace) = &self.namespace {
+            Api::<ConfigMap>::namespaced(client.clone(), namespace)
+                .delete(&config_map_name(&self.name), &DeleteParams::default()).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Starts tracking the entries, replacing any entries for the same objects.
+    pub fn add(&mut self, entries: Vec<InventoryEntry>) {
+        for entry in entries {
+            self.entries.retain(|existing| !existing.is_same_object(&entry));
+            self.entries.push(entry);
+        }
+    }
+
+    /// Stops tracking the entries.
+    pub fn remove(&mut self, entries: &[InventoryEntry]) {
+        self.entries.retain(|existing| !entries.iter().any(|entry| existing.is_same_object(entry)));
+    }
+
+    fn to_config_map(&self) -> anyhow::Result<ConfigMap> {
+        let mut labels = BTreeMap::new();
+        labels.insert(INVENTORY_OF_KEY.to_string(), self.name.clone());
+
+        let mut data = BTreeMap::new();
+        data.insert(INVENTORY_DATA_KEY.to_string(), serde_yaml::to_string(&self.entries)?);
+
+        Ok(ConfigMap {
+            metadata: ObjectMeta {
+                name: Some(config_map_name(&self.name)),
+                labels: Some(labels),
+                ..ObjectMeta::default()
+            },
+            data: Some(data),
+            ..ConfigMap::default()
+        })
+    }
+
+    fn from_config_map(config_map: &ConfigMap) -> anyhow::Result<Self> {
+        let name = config_map.labels().get(INVENTORY_OF_KEY)
+            .ok_or(anyhow!("inventory \"{}\" is missing the {} label", config_map.name_any(), INVENTORY_OF_KEY))?;
+        let entries = match config_map.data.as_ref().and_then(|data| data.get(INVENTORY_DATA_KEY)) {
+            Some(entries) => serde_yaml::from_str(entries)?,
+            None => Vec::new(),
+        };
+
+        Ok(Self {
+            name: name.clone(),
+            namespace: config_map.namespace(),
+            entries,
+        })
+    }
+}
+
+fn config_map_name(name: &str) -> String {
+    format!("mistletoe.inventory.{}", name)
+}
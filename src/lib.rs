@@ -1,6 +1,9 @@
+pub mod cluster;
 pub mod command;
 pub mod config;
+pub mod health;
 pub mod installation;
 pub mod instance;
+pub mod inventory;
 pub mod outputs;
 pub mod registry;
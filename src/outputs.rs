@@ -1,4 +1,6 @@
-use crate::installation::InstallResources;
+use crate::health::Health;
+use crate::installation::{InstallHealth, InstallResources, InstallSummary};
+use crate::registry::SearchResult;
 
 use std::path::{Path, PathBuf};
 
@@ -13,13 +15,17 @@ pub trait McOutputRaw {
 pub trait McOutputYaml {
     fn mc_output_yaml(self) -> anyhow::Result<String>;
 
-    fn mc_output_processed_yaml(self, name: String, version: Option<u32>)
+    fn mc_output_processed_yaml(self, name: String, version: Option<u32>, namespace: Option<String>)
         -> anyhow::Result<String>
         where Self : Sized
     {
-        InstallResources::from_str(&self.mc_output_yaml()?)?
-            .label_resources_with(&name, version)?
-            .to_string()
+        let resources = InstallResources::from_str(&self.mc_output_yaml()?)?
+            .label_resources_with(&name, version)?;
+
+        match namespace {
+            Some(namespace) => resources.with_default_namespace(&namespace).to_string(),
+            None => resources.to_string(),
+        }
     }
 }
 
@@ -27,6 +33,14 @@ pub trait McOutputList {
     fn mc_output_list(self) -> anyhow::Result<String>;
 }
 
+pub trait McOutputJson {
+    fn mc_output_json(self) -> anyhow::Result<String>;
+}
+
+pub trait McOutputTable {
+    fn mc_output_table(self) -> anyhow::Result<String>;
+}
+
 pub trait McOutputDir {
     fn mc_output_dir(self, path: &Path) -> anyhow::Result<()>;
 }
@@ -71,6 +85,128 @@ impl McOutputList for &Vec<DynamicObject> {
     }
 }
 
+impl McOutputYaml for &Vec<InstallSummary> {
+    fn mc_output_yaml(self) -> anyhow::Result<String> {
+        Ok(serde_yaml::to_string(self)?.trim().to_string())
+    }
+}
+
+impl McOutputJson for &Vec<InstallSummary> {
+    fn mc_output_json(self) -> anyhow::Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+impl McOutputTable for &Vec<InstallSummary> {
+    fn mc_output_table(self) -> anyhow::Result<String> {
+        let rows = self.iter()
+            .map(|install| vec![
+                install.name.clone(),
+                install.package.clone().unwrap_or("<unknown>".to_string()),
+                install.version.map(|version| version.to_string()).unwrap_or("-".to_string()),
+                install.status.clone().unwrap_or("-".to_string()),
+                if install.namespaces.is_empty() { "-".to_string() } else { install.namespaces.join(",") },
+                install.resource_count.to_string(),
+                install.last_applied.clone().unwrap_or("-".to_string()),
+            ])
+            .collect();
+
+        Ok(format_table(
+            &["NAME", "PACKAGE", "REVISION", "STATUS", "NAMESPACES", "RESOURCES", "LAST APPLIED"],
+            rows))
+    }
+}
+
+impl McOutputYaml for &Vec<SearchResult> {
+    fn mc_output_yaml(self) -> anyhow::Result<String> {
+        Ok(serde_yaml::to_string(self)?.trim().to_string())
+    }
+}
+
+impl McOutputJson for &Vec<SearchResult> {
+    fn mc_output_json(self) -> anyhow::Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+impl McOutputTable for &Vec<SearchResult> {
+    fn mc_output_table(self) -> anyhow::Result<String> {
+        let rows = self.iter()
+            .map(|result| vec![
+                result.registry.clone(),
+                result.package.clone(),
+                result.latest_version.clone().unwrap_or("-".to_string()),
+                result.description.clone().unwrap_or("-".to_string()),
+            ])
+            .collect();
+
+        Ok(format_table(&["REGISTRY", "PACKAGE", "LATEST", "DESCRIPTION"], rows))
+    }
+}
+
+impl McOutputYaml for &InstallHealth {
+    fn mc_output_yaml(self) -> anyhow::Result<String> {
+        Ok(serde_yaml::to_string(self)?.trim().to_string())
+    }
+}
+
+impl McOutputJson for &InstallHealth {
+    fn mc_output_json(self) -> anyhow::Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+impl McOutputTable for &InstallHealth {
+    fn mc_output_table(self) -> anyhow::Result<String> {
+        let rows = self.resources.iter()
+            .map(|status| vec![
+                status.kind.clone(),
+                status.name.clone(),
+                status.namespace.clone().unwrap_or("-".to_string()),
+                match status.health {
+                    Health::Ready => "Ready",
+                    Health::Progressing(_) => "Progressing",
+                    Health::Failed(_) => "Failed",
+                }.to_string(),
+                status.health.reason().unwrap_or("-").to_string(),
+            ])
+            .collect();
+
+        let mut output = format_table(&["KIND", "NAME", "NAMESPACE", "STATUS", "DETAILS"], rows);
+
+        let warnings: Vec<String> = self.resources.iter()
+            .flat_map(|status| status.warnings.iter()
+                .map(move |warning| format!("  {}/{}: {}", status.kind, status.name, warning)))
+            .collect();
+        if !warnings.is_empty() {
+            output += &format!("\n\nrecent warnings:\n{}", warnings.join("\n"));
+        }
+
+        Ok(output)
+    }
+}
+
+/// Lays out the rows in left-aligned columns sized to their widest cell.
+fn format_table(headers: &[&str], rows: Vec<Vec<String>>) -> String {
+    let mut widths: Vec<usize> = headers.iter().map(|header| header.len()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    std::iter::once(headers.iter().map(|header| header.to_string()).collect::<Vec<String>>())
+        .chain(rows)
+        .map(|row| row.iter().zip(&widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect::<Vec<String>>()
+            .join("   ")
+            .trim_end()
+            .to_string())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
 impl McOutputRaw for MistResult {
     fn mc_output_raw(self) -> anyhow::Result<String> {
         Ok(serialize_result(&self)?.trim().to_string())
@@ -1,15 +1,35 @@
 use crate::config::{MIST_HOME_LOCATION, RemoteLayout, ConfigLayout};
+use crate::instance::{MistPackageInstance, MistPackageRef};
 
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 
 use anyhow::anyhow;
 use git2::Repository;
 use once_cell::sync::Lazy;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
 pub static MIST_REGISTRIES_LOCATION: Lazy<PathBuf> = Lazy::new(||
     MIST_HOME_LOCATION.join(Path::new("registries")));
 
+/// Name of the index file at the root of a registry, see [RegistryIndex].
+pub const REGISTRY_INDEX_FILE: &'static str = "index.yaml";
+
+/// Package label holding a short description of the package.
+pub const DESCRIPTION_LABEL: &'static str = "mistletoe.dev/description";
+
+const PACKAGE_EXTENSION: &'static str = ".mist-pack.wasm";
+
+const INDEX_API_VERSION: &'static str = "mistletoe.dev/v1alpha1";
+const INDEX_KIND: &'static str = "MistRegistryIndex";
+
+// Serde's default attribute references values by function
+fn default_index_api_version() -> String { INDEX_API_VERSION.to_string() }
+fn default_index_kind() -> String { INDEX_KIND.to_string() }
+
 pub struct Remote {
     registry_name: String,
     layout: RemoteLayout,
@@ -50,6 +70,274 @@ impl Remote {
                 => GitRemote { url: git.url.clone() }.lookup_package(&self.registry_name, package, version),
         }
     }
+
+    /// Returns the index of the registry's local checkout.  Registries without an index file are
+    /// indexed on the fly, which loads every package in them.
+    pub fn index(&self) -> anyhow::Result<RegistryIndex> {
+        let local_path = self.get_local_registry_path();
+
+        match RegistryIndex::from_dir(&local_path)? {
+            Some(index) => Ok(index),
+            None => RegistryIndex::generate(&local_path),
+        }
+    }
+
+    /// Lists the versions of the package in the registry's local checkout, from oldest to newest.
+    /// These are the versions the index lists along with the ones in the package's directory, so
+    /// versions added since the index was last built are found too.
+    pub fn list_versions(&self, package: &Path) -> anyhow::Result<Vec<Version>> {
+        let local_path = self.get_local_registry_path();
+        let package_name = package.iter()
+            .map(|part| part.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        let indexed = RegistryIndex::from_dir(&local_path)?
+            .and_then(|index| index.lookup(&package_name).cloned());
+        let package_path = local_path.join(package);
+
+        if indexed.is_none() && !package_path.is_dir() {
+            return Err(anyhow!("could not find package at {}", package.display()));
+        }
+
+        let mut versions = match indexed {
+            Some(indexed) => indexed.versions.iter()
+                .map(|indexed| Version::parse(&indexed.version))
+                .collect::<Result<Vec<Version>, _>>()?,
+            None => Vec::new(),
+        };
+
+        if package_path.is_dir() {
+            let dir_name = package.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+
+            versions.extend(fs::read_dir(&package_path)?
+                .collect::<Result<Vec<fs::DirEntry>, _>>()?.iter()
+                .filter_map(|entry| entry.file_name().to_str()
+                    .and_then(|file_name| package_file_version(dir_name, file_name))
+                    .and_then(|version| Version::parse(version).ok())));
+        }
+
+        versions.sort();
+        versions.dedup();
+        Ok(versions)
+    }
+
+    fn get_local_registry_path(&self) -> PathBuf {
+        match &self.layout {
+            RemoteLayout::Git { name: _, git }
+                => GitRemote { url: git.url.clone() }.get_local_registry_path(&self.registry_name),
+        }
+    }
+}
+
+/// Index of the packages in a registry, kept in [REGISTRY_INDEX_FILE] at its root.
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegistryIndex {
+    #[serde(default = "default_index_api_version")]
+    api_version: String,
+    #[serde(default = "default_index_kind")]
+    kind: String,
+    /// The packages, sorted by path.
+    #[serde(default)]
+    pub packages: Vec<IndexedPackage>,
+}
+
+/// A package in a [RegistryIndex], with the metadata of its latest version.
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexedPackage {
+    /// Path of the package in the registry, e.g. `examples/namespace-example`.
+    pub package: String,
+    /// Name the package gives itself.
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+    /// The available versions, from oldest to newest.
+    pub versions: Vec<IndexedVersion>,
+}
+
+/// A single version of an [IndexedPackage].
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+pub struct IndexedVersion {
+    pub version: String,
+    /// Digest of the package file, e.g. `sha256:...`.
+    pub digest: String,
+}
+
+impl RegistryIndex {
+    pub fn new(packages: Vec<IndexedPackage>) -> Self {
+        Self {
+            api_version: INDEX_API_VERSION.to_string(),
+            kind: INDEX_KIND.to_string(),
+            packages,
+        }
+    }
+
+    pub fn from_str(index_str: &str) -> anyhow::Result<Self> {
+        Ok(serde_yaml::from_str(index_str)?)
+    }
+
+    /// Reads the index file at the root of the registry, if there is one.
+    pub fn from_dir(root: &Path) -> anyhow::Result<Option<Self>> {
+        let index_path = root.join(REGISTRY_INDEX_FILE);
+
+        if !index_path.is_file() {
+            return Ok(None);
+        }
+
+        Ok(Some(Self::from_str(&fs::read_to_string(index_path)?)?))
+    }
+
+    /// Indexes the registry at the given root by walking it for package files.  A package lives in
+    /// its own directory as `<directory name>-<version>.mist-pack.wasm`, and each version is
+    /// loaded to read the package's metadata.
+    pub fn generate(root: &Path) -> anyhow::Result<Self> {
+        let mut packages = BTreeMap::new();
+        collect_package_files(root, root, &mut packages)?;
+
+        let mut indexed = Vec::new();
+        for (package, mut versions) in packages {
+            versions.sort_by(|(v1, _), (v2, _)| v1.cmp(v2));
+
+            let (_, latest_path) = versions.last().unwrap();
+            let info = MistPackageInstance::load(&MistPackageRef::Local(latest_path.clone()))?.info()?;
+            let labels: BTreeMap<String, String> = info.labels.unwrap_or_default().into_iter().collect();
+
+            indexed.push(IndexedPackage {
+                package,
+                name: info.name,
+                description: labels.get(DESCRIPTION_LABEL).cloned(),
+                labels,
+                versions: versions.iter()
+                    .map(|(version, path)| Ok(IndexedVersion {
+                        version: version.to_string(),
+                        digest: file_digest(path)?,
+                    }))
+                    .collect::<anyhow::Result<Vec<IndexedVersion>>>()?,
+            });
+        }
+
+        Ok(Self::new(indexed))
+    }
+
+    pub fn to_string(&self) -> anyhow::Result<String> {
+        Ok(serde_yaml::to_string(self)?)
+    }
+
+    /// Writes the index file at the root of the registry.
+    pub fn write_to_dir(&self, root: &Path) -> anyhow::Result<()> {
+        Ok(fs::write(root.join(REGISTRY_INDEX_FILE), self.to_string()?)?)
+    }
+
+    /// Returns the package at the given path in the registry.
+    pub fn lookup(&self, package: &str) -> Option<&IndexedPackage> {
+        self.packages.iter().find(|indexed| indexed.package == package)
+    }
+}
+
+impl IndexedPackage {
+    /// Returns the newest version.
+    pub fn latest(&self) -> Option<&IndexedVersion> {
+        self.versions.last()
+    }
+
+    pub fn lookup_version(&self, version: &str) -> Option<&IndexedVersion> {
+        self.versions.iter().find(|indexed| indexed.version == version)
+    }
+
+    /// Whether the package has all of the given labels and, if there's a keyword, whether its
+    /// path, name or one of its label values contains it, ignoring case.
+    pub fn matches(&self, keyword: Option<&str>, labels: &[(String, String)]) -> bool {
+        let has_labels = labels.iter()
+            .all(|(key, value)| self.labels.get(key) == Some(value));
+
+        let has_keyword = match keyword {
+            Some(keyword) => {
+                let keyword = keyword.to_lowercase();
+                [&self.package, &self.name].into_iter()
+                    .chain(self.labels.values())
+                    .any(|text| text.to_lowercase().contains(&keyword))
+            },
+            None => true,
+        };
+
+        has_labels && has_keyword
+    }
+}
+
+/// A package found by searching the configured registries.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResult {
+    pub registry: String,
+    /// Path of the package in the registry.
+    pub package: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latest_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+impl SearchResult {
+    pub fn new(registry: &str, package: &IndexedPackage) -> Self {
+        Self {
+            registry: registry.to_string(),
+            package: package.package.clone(),
+            latest_version: package.latest().map(|latest| latest.version.clone()),
+            description: package.description.clone(),
+        }
+    }
+}
+
+/// Finds the package files under `dir`, grouped by their package path relative to `root`.  Hidden
+/// directories, like `.git`, are skipped.
+fn collect_package_files(root: &Path, dir: &Path, packages: &mut BTreeMap<String, Vec<(Version, PathBuf)>>)
+    -> anyhow::Result<()>
+{
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let file_name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+
+        if file_name.starts_with('.') {
+            continue;
+        }
+
+        if path.is_dir() {
+            collect_package_files(root, &path, packages)?;
+            continue;
+        }
+
+        let dir_name = dir.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+        let version = match package_file_version(dir_name, file_name) {
+            Some(version) => Version::parse(version)
+                .map_err(|e| anyhow!("package file {} has an invalid version: {}", path.display(), e))?,
+            None => continue,
+        };
+
+        let package = dir.strip_prefix(root)?.iter()
+            .map(|part| part.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        packages.entry(package).or_default().push((version, path));
+    }
+
+    Ok(())
+}
+
+/// Returns the version in the name of a package file, which is named after the package's
+/// directory as `<directory name>-<version>.mist-pack.wasm`.
+fn package_file_version<'a>(dir_name: &str, file_name: &'a str) -> Option<&'a str> {
+    file_name.strip_suffix(PACKAGE_EXTENSION)
+        .and_then(|stem| stem.strip_prefix(dir_name))
+        .and_then(|rest| rest.strip_prefix('-'))
+}
+
+fn file_digest(path: &Path) -> anyhow::Result<String> {
+    let digest = Sha256::digest(fs::read(path)?);
+    Ok(format!("sha256:{}", digest.iter().map(|byte| format!("{:02x}", byte)).collect::<String>()))
 }
 
 struct GitRemote {
@@ -93,8 +381,8 @@ impl GitRemote {
     fn lookup_package(&self, registry_name: &str, package: &Path, version: &str) -> Option<PathBuf> {
         let package_path = self.get_local_registry_path(registry_name)
             .join(&package)
-            .join(format!("{}-{}.mist-pack.wasm",
-                package.file_name().unwrap().to_str().unwrap(), version));
+            .join(format!("{}-{}{}",
+                package.file_name().unwrap().to_str().unwrap(), version, PACKAGE_EXTENSION));
         
         if package_path.exists() { Some(package_path) } else { None }
     }
@@ -127,3 +415,44 @@ pub fn process_registries(config: &ConfigLayout) -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_registry_index() {
+        let index_str = indoc! {"
+            apiVersion: mistletoe.dev/v1alpha1
+            kind: MistRegistryIndex
+            packages:
+            - package: examples/namespace-example
+              name: namespace-example
+              description: Creates a namespace
+              labels:
+                mistletoe.dev/description: Creates a namespace
+                mistletoe.dev/group: mistletoe-examples
+              versions:
+              - version: 0.1.1
+                digest: sha256:1111
+              - version: 0.1.2
+                digest: sha256:2222
+        "};
+
+        let index = RegistryIndex::from_str(index_str).unwrap();
+        let package = index.lookup("examples/namespace-example").unwrap();
+        assert_eq!("0.1.2", package.latest().unwrap().version);
+        assert_eq!("sha256:1111", package.lookup_version("0.1.1").unwrap().digest);
+        assert!(index.lookup("examples/missing").is_none());
+
+        let group = ("mistletoe.dev/group".to_string(), "mistletoe-examples".to_string());
+        assert!(package.matches(Some("NAMESPACE"), &[]));
+        assert!(package.matches(Some("examples"), std::slice::from_ref(&group)));
+        assert!(package.matches(None, &[group]));
+        assert!(!package.matches(Some("postgres"), &[]));
+        assert!(!package.matches(None, &[("mistletoe.dev/group".to_string(), "databases".to_string())]));
+
+        assert_eq!(index, RegistryIndex::from_str(&index.to_string().unwrap()).unwrap());
+    }
+}